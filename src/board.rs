@@ -0,0 +1,704 @@
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+use crate::divisor::divisor_board;
+use crate::error::Error;
+use crate::heights::Heights;
+use crate::memo::Memo;
+use crate::poset::Poset;
+use crate::rules::{PoisonMode, Ruleset};
+use crate::state::{cell_words, State, MAX_CELLS};
+
+/// d 次元の座標。成分 k が k 番目の軸方向の位置を表す
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Coord(pub Vec<u32>);
+
+impl fmt::Display for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (k, c) in self.0.iter().enumerate() {
+            if k > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", c)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Coord {
+    /// 各辺の長さが dims の箱での混合基数インデックス i（第 0 軸が最も速く変わる）を座標にする
+    pub fn from_index(dims: &[u32], i: u32) -> Coord {
+        let mut rest = i;
+        Coord(
+            dims.iter()
+                .map(|&d| {
+                    let c = rest % d;
+                    rest /= d;
+                    c
+                })
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for Coord {
+    type Err = Error;
+
+    /// "1,0,2" や "(1, 0, 2)" のような文字列から座標を作る
+    fn from_str(s: &str) -> Result<Coord, Error> {
+        s.trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split(',')
+            .map(|c| c.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map(Coord)
+            .map_err(|_| Error::InvalidPosition(format!("座標を解釈できません: {}", s)))
+    }
+}
+
+/// Chomp の盤面。ブロックの集合に半順序を入れたもので、ブロックを選ぶとそれ以上のブロックが
+/// すべて取り除かれる。通常は各軸方向のブロック数を実行時に保持する d 次元の箱で、
+/// ブロックのインデックスは第 0 軸が最も速く変わる混合基数表記で座標に対応する。
+/// 任意の有限半順序集合（Hasse 図）からも作れ、その場合は座標の代わりに要素の名前を使う。
+/// 勝敗の規約や毒ブロック・選べないブロックの配置も盤面と一緒に持ち、合法手や終端の判定はそれに従う
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Board {
+    poset: Poset,
+    /// 箱の盤面なら各辺の長さ
+    dims: Option<Vec<u32>>,
+    name: String,
+    ruleset: Ruleset,
+    /// 毒ブロックのインデックス。取り除くブロック群に毒ブロックを含む手は毒を食べる手になる
+    poison: Vec<u32>,
+    poison_mode: PoisonMode,
+    /// 選べないブロックのインデックス。他のブロックを選んだときに一緒に取り除かれることはある
+    forbidden: Vec<u32>,
+    /// poison と forbidden を State::from_words の語の並びにしたもの。合法手を求めるたびに引く
+    poison_words: Vec<u64>,
+    forbidden_words: Vec<u64>,
+}
+
+impl Board {
+    /// 各辺の長さから毒ブロック形式の箱の盤面を作る。用意している最大の状態型にも収まらない大きさはエラー
+    pub fn new(dims: Vec<u32>) -> Result<Board, Error> {
+        if dims.is_empty() || dims.contains(&0) {
+            return Err(Error::InvalidShape(format!(
+                "盤面の次元は 1 以上、各辺は 1 以上である必要があります: {:?}",
+                dims
+            )));
+        }
+        let cells = dims
+            .iter()
+            .try_fold(1u64, |acc, &d| acc.checked_mul(d as u64))
+            .unwrap_or(u64::MAX);
+        if cells > MAX_CELLS as u64 {
+            return Err(Error::TooManyCells { cells, capacity: MAX_CELLS });
+        }
+        let name = dims.iter().map(u32::to_string).collect::<Vec<_>>().join("x");
+        let mut board = Board::from_poset(name, Poset::product(&dims))?;
+        board.dims = Some(dims);
+        Ok(board)
+    }
+
+    /// 任意の有限半順序集合から毒ブロック形式の盤面を作る。毒ブロックは極小元
+    pub fn from_poset(name: String, poset: Poset) -> Result<Board, Error> {
+        if poset.len() > MAX_CELLS {
+            return Err(Error::TooManyCells { cells: poset.len() as u64, capacity: MAX_CELLS });
+        }
+        let tot = poset.len();
+        let board = Board {
+            poset,
+            dims: None,
+            name,
+            ruleset: Ruleset::default(),
+            poison: Vec::new(),
+            poison_mode: PoisonMode::default(),
+            forbidden: Vec::new(),
+            poison_words: Vec::new(),
+            forbidden_words: cell_words(tot, []),
+        };
+        let poison = board.default_poison();
+        Ok(board.with_poison(poison))
+    }
+
+    /// 盤面とブロックの名前を付け替えた盤面を返す。順序や箱の形は変わらない
+    pub fn relabel(mut self, name: String, labels: Vec<String>) -> Board {
+        self.name = name;
+        self.poset.relabel(labels);
+        self
+    }
+
+    /// "2x3x19" や "2x2x2x5" のような文字列から箱の盤面を作る。
+    /// "poset:<ファイル>" なら Hasse 図のファイル（書式は Poset::parse）から、
+    /// "div:<n>" なら n の約数の盤面（約数 Chomp）を作る
+    pub fn parse(s: &str) -> Result<Board, Error> {
+        if let Some(n) = s.strip_prefix("div:") {
+            let n = n
+                .parse()
+                .map_err(|_| Error::InvalidShape(format!("約数 Chomp の n を解釈できません: {}", n)))?;
+            return divisor_board(n);
+        }
+        if let Some(path) = s.strip_prefix("poset:") {
+            let text = fs::read_to_string(path)
+                .map_err(|e| Error::Io(format!("{} を読めません: {}", path, e)))?;
+            return Board::from_poset(path.to_string(), Poset::parse(&text)?);
+        }
+        let dims: Vec<u32> = s
+            .split('x')
+            .map(|d| d.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| Error::InvalidShape(format!("盤面サイズを解釈できません: {}", s)))?;
+        Board::new(dims)
+    }
+
+    /// 規約の既定の毒ブロック（毒ブロック形式では極小元で、箱なら原点。それ以外の規約ではなし）
+    fn default_poison(&self) -> Vec<u32> {
+        match self.ruleset {
+            Ruleset::Poison => self.poset.minimal(),
+            Ruleset::Normal | Ruleset::Misere => Vec::new(),
+        }
+    }
+
+    /// 規約を変えた盤面を返す。毒ブロックはその規約の既定の配置に戻る
+    pub fn with_ruleset(mut self, ruleset: Ruleset) -> Board {
+        self.ruleset = ruleset;
+        let poison = self.default_poison();
+        self.with_poison(poison)
+    }
+
+    /// 毒ブロックの配置を変えた盤面を返す
+    pub fn with_poison(mut self, cells: Vec<u32>) -> Board {
+        self.poison_words = cell_words(self.tot(), cells.iter().copied());
+        self.poison = cells;
+        self
+    }
+
+    /// 毒ブロックを選んだときの扱いを変えた盤面を返す
+    pub fn with_poison_mode(mut self, mode: PoisonMode) -> Board {
+        self.poison_mode = mode;
+        self
+    }
+
+    /// 選べないブロックを変えた盤面を返す
+    pub fn with_forbidden(mut self, cells: Vec<u32>) -> Board {
+        self.forbidden_words = cell_words(self.tot(), cells.iter().copied());
+        self.forbidden = cells;
+        self
+    }
+
+    /// ブロックの指定を解釈してインデックスを返す。ブロックの名前（半順序集合の要素の名前や
+    /// 約数 Chomp の約数）か、箱なら "1,0,2" のような座標で指定する。盤面にないブロックはエラー
+    pub fn parse_cell(&self, s: &str) -> Result<u32, Error> {
+        let index = match (self.poset.index_of(s.trim()), &self.dims) {
+            (Some(i), _) => Some(i),
+            (None, Some(_)) => self.coord_to_index(&s.parse()?),
+            (None, None) => None,
+        };
+        index.ok_or_else(|| Error::InvalidPosition(format!("ブロック {} は盤面 {} にありません", s, self)))
+    }
+
+    /// 勝敗の規約
+    pub fn ruleset(&self) -> Ruleset {
+        self.ruleset
+    }
+
+    /// 毒ブロックを選んだときの扱い
+    pub fn poison_mode(&self) -> PoisonMode {
+        self.poison_mode
+    }
+
+    /// 毒ブロックのインデックス
+    pub fn poison(&self) -> &[u32] {
+        &self.poison
+    }
+
+    /// 選べないブロックのインデックス
+    pub fn forbidden(&self) -> &[u32] {
+        &self.forbidden
+    }
+
+    /// ブロックの半順序集合
+    pub fn poset(&self) -> &Poset {
+        &self.poset
+    }
+
+    /// 箱の盤面なら各軸方向のブロック数
+    pub fn dims(&self) -> Option<&[u32]> {
+        self.dims.as_deref()
+    }
+
+    /// 全ブロック数
+    pub fn tot(&self) -> u32 {
+        self.poset.len()
+    }
+
+    /// 状態型 S で全ブロックを表現できるか確認する。
+    /// 表現できない場合にビットが黙って桁あふれしないよう、計算の前に呼ぶ
+    pub fn check_capacity<S: State>(&self) -> Result<(), Error> {
+        if self.tot() > S::CAPACITY {
+            return Err(Error::TooManyCells { cells: self.tot() as u64, capacity: S::CAPACITY });
+        }
+        Ok(())
+    }
+
+    /// 全ブロックが存在している状態 → 下位 tot ビットがすべて 1
+    pub fn full_state<S: State>(&self) -> S {
+        S::low_bits(self.tot())
+    }
+
+    /// ブロック i の表示名（箱なら座標 "(x, y, z)"、一般の半順序集合なら要素の名前）
+    pub fn cell_name(&self, i: u32) -> &str {
+        self.poset.label(i)
+    }
+
+    /// インデックス -> 座標への変換。箱の盤面でなければ None
+    pub fn index_to_coord(&self, i: u32) -> Option<Coord> {
+        Some(Coord::from_index(self.dims.as_ref()?, i))
+    }
+
+    /// 座標 -> インデックスへの変換。箱の盤面でないか、盤面の外の座標なら None
+    pub fn coord_to_index(&self, coord: &Coord) -> Option<u32> {
+        let dims = self.dims.as_ref()?;
+        if coord.0.len() != dims.len() {
+            return None;
+        }
+        let mut index = 0;
+        for (&c, &d) in coord.0.iter().zip(dims).rev() {
+            if c >= d {
+                return None;
+            }
+            index = index * d + c;
+        }
+        Some(index)
+    }
+
+    /// 箱の盤面 sub を原点をそろえて自分の中に置いたときの、sub のブロック i が移る自分のブロックの
+    /// インデックスの表。sub が自分の部分箱でないか、規約や、sub のブロックの毒ブロック・選べないブロックの
+    /// 扱いが自分と違えば None。None でなければ sub の局面は表で移した自分の局面と勝敗が同じ
+    pub fn embedding(&self, sub: &Board) -> Option<Vec<u32>> {
+        let (dims, sub_dims) = (self.dims()?, sub.dims()?);
+        if dims.len() != sub_dims.len()
+            || dims.iter().zip(sub_dims).any(|(d, s)| s > d)
+            || self.ruleset != sub.ruleset
+            || self.poison_mode != sub.poison_mode
+        {
+            return None;
+        }
+        let mut map = Vec::with_capacity(sub.tot() as usize);
+        for i in 0..sub.tot() {
+            let j = self.coord_to_index(&sub.index_to_coord(i)?)?;
+            if sub.is_poison(i) != self.is_poison(j) || sub.is_forbidden(i) != self.is_forbidden(j) {
+                return None;
+            }
+            map.push(j);
+        }
+        Some(map)
+    }
+
+    /// 盤面 sub で解いた表 from の局面を自分の局面に移して表 into に書き、移した局面の数を返す。
+    /// embedding で sub を自分の中に置けなければ何もしない。sub の局面を置いた局面は勝敗が同じなので、
+    /// 解き直さずに済む
+    pub fn carry_over<S: State>(&self, sub: &Board, from: &dyn Memo<S>, into: &dyn Memo<S>) -> u64 {
+        let Some(map) = self.embedding(sub) else {
+            return 0;
+        };
+        // 最後の軸だけが伸びたならインデックスは変わらない
+        let same = map.iter().enumerate().all(|(i, &j)| i as u32 == j);
+        let mut count = 0;
+        from.for_each_entry(&mut |state, win| {
+            let moved = if same {
+                state
+            } else {
+                (0..sub.tot()).filter(|&i| state.has(i)).fold(S::zero(), |s, i| s | S::bit(map[i as usize]))
+            };
+            into.insert(moved, win);
+            count += 1;
+        });
+        count
+    }
+
+    /// 箱の盤面の各辺の長さ。一般の半順序集合の盤面ではエラー
+    fn box_dims(&self) -> Result<&[u32], Error> {
+        self.dims().ok_or_else(|| {
+            Error::InvalidShape(format!("盤面 {} は箱ではないので高さ行列で表せません", self))
+        })
+    }
+
+    /// 最後の軸方向のブロック数（柱の高さの上限）
+    fn height_dim(&self) -> Result<u32, Error> {
+        Ok(*self.box_dims()?.last().unwrap())
+    }
+
+    /// 最後の軸以外からなる格子の柱の本数
+    fn base_len(&self) -> Result<u32, Error> {
+        Ok(self.tot() / self.height_dim()?)
+    }
+
+    /// 状態を高さ行列に変換する。状態が下に閉じていないか、箱の盤面でなければエラー
+    pub fn state_to_heights<S: State>(&self, state: S) -> Result<Heights, Error> {
+        let (height, base) = (self.height_dim()?, self.base_len()?);
+        let mut heights = Vec::with_capacity(base as usize);
+        for b in 0..base {
+            // 柱 b のブロックは b, b + base, b + 2*base, ... のインデックスを持つ
+            let h = (0..height).take_while(|&z| state.has(b + z * base)).count() as u32;
+            if (h..height).any(|z| state.has(b + z * base)) {
+                return Err(Error::InvalidPosition(format!(
+                    "柱 {} に宙に浮いたブロックがあります",
+                    self.cell_name(b)
+                )));
+            }
+            heights.push(h);
+        }
+        let heights = Heights::new(self.box_dims()?[0], heights);
+        self.check_heights(&heights)?;
+        Ok(heights)
+    }
+
+    /// 高さ行列を状態に変換する。高さが盤面に収まらないか単調非増加でなければエラー
+    pub fn heights_to_state<S: State>(&self, heights: &Heights) -> Result<S, Error> {
+        self.check_heights(heights)?;
+        let base = self.base_len()?;
+        let mut state = S::zero();
+        for (b, &h) in heights.as_slice().iter().enumerate() {
+            for z in 0..h {
+                state = state | S::bit(b as u32 + z * base);
+            }
+        }
+        Ok(state)
+    }
+
+    /// 高さ行列が盤面の order ideal を表しているか確認する
+    fn check_heights(&self, heights: &Heights) -> Result<(), Error> {
+        let h = heights.as_slice();
+        let (height, base) = (self.height_dim()?, self.base_len()?);
+        if h.len() != base as usize {
+            return Err(Error::InvalidPosition(format!(
+                "柱の本数 {} が盤面 {} の {} 本と一致しません",
+                h.len(),
+                self, base
+            )));
+        }
+        let dims = self.box_dims()?;
+        let base_dims = &dims[..dims.len() - 1];
+        for b in 0..h.len() {
+            if h[b] > height {
+                return Err(Error::InvalidPosition(format!(
+                    "柱 {} の高さ {} が盤面の高さ {} を超えています",
+                    self.cell_name(b as u32), h[b], height
+                )));
+            }
+            // 各軸方向の一つ手前の柱より高くてはいけない
+            let mut stride = 1;
+            let mut rest = b;
+            for &d in base_dims {
+                let d = d as usize;
+                if !rest.is_multiple_of(d) && h[b] > h[b - stride] {
+                    return Err(Error::InvalidPosition(format!(
+                        "柱 {} が手前の柱より高く、高さが単調非増加になっていません",
+                        self.cell_name(b as u32)
+                    )));
+                }
+                rest /= d;
+                stride *= d;
+            }
+        }
+        Ok(())
+    }
+
+    /// 局面の指定を解釈して状態を返す。次のいずれかの書式で、# 以降はコメント:
+    ///   高さ行列 … "2,2/2,1" のように柱の高さを "," か空白で、行を "/" か改行で区切る（箱の盤面のみ）
+    ///   cells:… … "cells:0,0;1,0;0,1" のように残っているブロックを ";" か改行で区切って並べる
+    /// 局面は下に閉じていて、毒ブロックをすべて含んでいなければならない
+    pub fn parse_position<S: State>(&self, text: &str) -> Result<S, Error> {
+        let text: Vec<&str> = text.lines().map(|line| line.split('#').next().unwrap()).collect();
+        let text = text.join("\n");
+        let state = match text.trim().strip_prefix("cells:") {
+            Some(cells) => cells
+                .split([';', '\n'])
+                .filter(|c| !c.trim().is_empty())
+                .map(|c| self.parse_cell(c).map(S::bit))
+                .try_fold(S::zero(), |state, bit| bit.map(|bit| state | bit))?,
+            None => self.heights_to_state(&self.parse_heights(&text)?)?,
+        };
+        self.check_position(state)?;
+        Ok(state)
+    }
+
+    /// 局面を parse_position で読み戻せる文字列にする。箱の盤面なら高さ行列、
+    /// それ以外なら "cells:" に続けて残っているブロックの名前を ";" で区切って並べる
+    pub fn format_position<S: State>(&self, state: S) -> Result<String, Error> {
+        if self.dims.is_some() {
+            return Ok(self.state_to_heights(state)?.to_string());
+        }
+        let cells: Vec<&str> = (0..self.tot()).filter(|&i| state.has(i)).map(|i| self.cell_name(i)).collect();
+        Ok(format!("cells:{}", cells.join(";")))
+    }
+
+    /// 高さ行列の文字列を解釈する。各行の柱の数が盤面と一致しなければエラー
+    fn parse_heights(&self, text: &str) -> Result<Heights, Error> {
+        // 1 次元の盤面では柱が 1 本だけなので、1 行の柱の数は第 0 軸の長さと柱の本数の小さい方
+        let row_len = self.box_dims()?[0].min(self.base_len()?);
+        let mut heights = Vec::new();
+        for row in text.split(['/', '\n']).filter(|r| !r.trim().is_empty()) {
+            let entries: Vec<u32> = row
+                .split([',', ' ', '\t'])
+                .filter(|h| !h.is_empty())
+                .map(|h| h.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| Error::InvalidPosition(format!("高さ行列の行を解釈できません: {}", row.trim())))?;
+            if entries.len() != row_len as usize {
+                return Err(Error::InvalidPosition(format!(
+                    "高さ行列の行 {} の柱の数が {} 本ではありません",
+                    row.trim(),
+                    row_len
+                )));
+            }
+            heights.extend(entries);
+        }
+        Ok(Heights::new(self.box_dims()?[0], heights))
+    }
+
+    /// 状態が毒ブロックをすべて含むか。毒ブロックは取り除けないので、含まない order ideal
+    /// （毒のある規約での空の盤面など）は局面ではない
+    pub fn contains_poison<S: State>(&self, state: S) -> bool {
+        let poison: S = S::from_words(&self.poison_words);
+        state & poison == poison
+    }
+
+    /// ブロック i が毒ブロックか
+    pub fn is_poison(&self, i: u32) -> bool {
+        self.poison_words[(i / 64) as usize] >> (i % 64) & 1 != 0
+    }
+
+    /// ブロック i が選べないブロックか
+    pub fn is_forbidden(&self, i: u32) -> bool {
+        self.forbidden_words[(i / 64) as usize] >> (i % 64) & 1 != 0
+    }
+
+    /// 状態が局面として正しいか（下に閉じていて毒ブロックをすべて含むか）確認する
+    pub fn check_position<S: State>(&self, state: S) -> Result<(), Error> {
+        if self.tot() < S::CAPACITY && !(state & !self.full_state::<S>()).is_zero() {
+            return Err(Error::InvalidPosition("盤面の外のブロックを含んでいます".to_string()));
+        }
+        // 残っているブロックより小さいブロックがすべて残っていること、
+        // つまり取り除かれたブロック以上のブロックがどれも残っていないことを確かめる
+        for i in (0..self.tot()).filter(|&i| !state.has(i)) {
+            if let Some(j) = self.poset.up_set(i).iter().find(|&&j| state.has(j)) {
+                return Err(Error::InvalidPosition(format!(
+                    "ブロック {} が残っているのに、それより小さいブロック {} がありません",
+                    self.cell_name(*j),
+                    self.cell_name(i)
+                )));
+            }
+        }
+        if let Some(&p) = self.poison.iter().find(|&&p| !state.has(p)) {
+            return Err(Error::InvalidPosition(format!("毒ブロック {} がありません", self.cell_name(p))));
+        }
+        Ok(())
+    }
+
+    /// 選んだブロック chosen 以上のブロック群（箱なら座標が chosen 以上のブロック群、
+    /// 一般の半順序集合なら Hasse 図で chosen から辿れるブロック群）を取り除くためのマスクを返す
+    pub fn removal_mask<S: State>(&self, chosen: u32) -> S {
+        self.poset.up_mask(chosen)
+    }
+
+    /// 現在の状態 state（各ブロックの存在をビットで表現）から、合法な手を返す。
+    /// 手は (chosen: ブロックのインデックス, new_state: S) の組として返す。
+    /// 選べないブロックを選ぶ手と、取り除くブロック群に残っている毒ブロックを含む手は含まない。
+    pub fn legal_moves<S: State>(&self, state: S) -> Vec<(u32, S)> {
+        let poison = self.live_poison(state);
+        let mut moves = Vec::new();
+        for i in 0..self.tot() {
+            // ブロック i が存在していて、選べるブロックかチェック
+            if state.has(i) && !self.is_forbidden(i) {
+                let rm_mask: S = self.removal_mask(i);
+                // もし取り除くブロック群に毒ブロックが含まれていたら不合法（または即負け）
+                if !(rm_mask & poison).is_zero() {
+                    continue;
+                }
+                let new_state = state & !rm_mask;
+                moves.push((i, new_state));
+            }
+        }
+        moves
+    }
+
+    /// 毒を食べる手（取り除くブロック群に残っている毒ブロックを含む手）のブロックを返す。
+    /// PoisonMode::Loses ではこれらの手も選べ、選んだプレイヤーがその場で負ける。
+    /// ほかに合法手があれば負ける手を選ぶ理由はないが、毒を食べる手しか残っていない局面は
+    /// Forbidden では合法手のない局面として規約で勝敗が決まる（misère なら手番の勝ち）のに対し、
+    /// Loses では手番のプレイヤーの負けになる（wins_without_moves）
+    pub fn poisoned_moves<S: State>(&self, state: S) -> Vec<u32> {
+        if self.poison_mode == PoisonMode::Forbidden {
+            return Vec::new();
+        }
+        let poison = self.live_poison(state);
+        (0..self.tot())
+            .filter(|&i| state.has(i) && !self.is_forbidden(i))
+            .filter(|&i| !(self.removal_mask::<S>(i) & poison).is_zero())
+            .collect()
+    }
+
+    /// legal_moves のない局面 state で手番のプレイヤーが勝つか。PoisonMode::Loses で毒を食べる手が
+    /// 残っていれば、手番のプレイヤーはそれを選ぶしかなくその場で負ける。そうでなければ規約で決まる
+    pub fn wins_without_moves<S: State>(&self, state: S) -> bool {
+        self.poisoned_moves(state).is_empty() && self.ruleset.wins_without_moves()
+    }
+
+    /// state に残っている毒ブロック。取り除くブロック群がこれと交われば毒を食べる手
+    fn live_poison<S: State>(&self, state: S) -> S {
+        state & S::from_words(&self.poison_words)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memo::DenseMemo;
+    use crate::rank::Ranker;
+    use crate::solver::{retrograde, retrograde_into, win};
+    use crate::state::Bits;
+    use dashmap::DashMap;
+
+    #[test]
+    fn index_to_coord_inverts_coord_to_index() {
+        let board = Board::new(vec![2, 3, 4]).unwrap();
+        for i in 0..board.tot() {
+            let coord = board.index_to_coord(i).unwrap();
+            assert_eq!(board.coord_to_index(&coord), Some(i));
+        }
+        assert_eq!(board.index_to_coord(7), Some(Coord(vec![1, 0, 1])));
+    }
+
+    #[test]
+    fn legal_moves_skip_forbidden_and_poisoned_cells() {
+        // 原点が毒で (1, 0) は選べない 3x2 の盤面。wide な状態型でも同じ手になる
+        let board = Board::new(vec![3, 2]).unwrap().with_forbidden(vec![1]).with_poison_mode(PoisonMode::Loses);
+        let moves: Vec<u32> = board.legal_moves(board.full_state::<u128>()).iter().map(|&(mv, _)| mv).collect();
+        assert_eq!(moves, [2, 3, 4, 5]);
+        let wide: Vec<(u32, Bits<4>)> = board.legal_moves(board.full_state());
+        assert_eq!(wide.iter().map(|&(mv, _)| mv).collect::<Vec<_>>(), moves);
+        assert_eq!(board.poisoned_moves(board.full_state::<u128>()), [0]);
+        assert!(board.contains_poison(1u128) && !board.contains_poison(2u128));
+    }
+
+    /// Hasse 図 a < b < c, a < d の盤面
+    fn poset_board() -> Board {
+        Board::from_poset("p".to_string(), Poset::parse("a b\nb c\na d\n").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn positions_round_trip_through_format_position() {
+        let boxes = [vec![3, 3], vec![2, 2, 3], vec![5]].map(|dims| Board::new(dims).unwrap());
+        for board in boxes.into_iter().chain([poset_board()]) {
+            let mut positions = 0;
+            for state in (0u128..1 << board.tot()).filter(|&s| board.check_position(s).is_ok()) {
+                let text = board.format_position(state).unwrap();
+                assert_eq!(board.parse_position::<u128>(&text).unwrap(), state, "{} {}", board, text);
+                positions += 1;
+            }
+            assert!(positions > 1);
+        }
+    }
+
+    #[test]
+    fn parses_height_matrices() {
+        let board = Board::new(vec![2, 2, 3]).unwrap();
+        let full: u128 = board.full_state();
+        assert_eq!(board.parse_position::<u128>("3,3/3,3").unwrap(), full);
+        // 行は改行でも区切れ、# 以降はコメント
+        assert_eq!(board.parse_position::<u128>("3 3  # 1 行目\n3,3\n").unwrap(), full);
+        assert_eq!(board.format_position(board.parse_position::<u128>("2,1/1,0").unwrap()).unwrap(), "2,1/1,0");
+        // 1 次元の盤面は柱 1 本の高さだけ
+        let line = Board::new(vec![5]).unwrap();
+        assert_eq!(line.parse_position::<u128>("3").unwrap(), 0b111);
+        assert_eq!(line.format_position(0b11u128).unwrap(), "2");
+    }
+
+    #[test]
+    fn parses_cell_lists() {
+        let board = Board::new(vec![3, 3]).unwrap();
+        assert_eq!(board.parse_position::<u128>("cells:0,0;1,0;0,1").unwrap(), 0b1011);
+        assert_eq!(board.parse_position::<u128>("cells:(0, 0)\n(1, 0)\n").unwrap(), 0b11);
+        let poset = poset_board();
+        let state: u128 = poset.parse_position("cells:a;b;d").unwrap();
+        assert_eq!(poset.format_position(state).unwrap(), "cells:a;b;d");
+        assert!(matches!(poset.parse_position::<u128>("cells:a;x"), Err(Error::InvalidPosition(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        let board = Board::new(vec![2, 2, 3]).unwrap();
+        let rejects = |text: &str| matches!(board.parse_position::<u128>(text), Err(Error::InvalidPosition(_)));
+        // 宙に浮いたブロック、単調非増加でない高さ、盤面を超える高さ
+        assert!(rejects("cells:0,0,0;0,0,2"));
+        assert!(rejects("1,2/1,1"));
+        assert!(rejects("2,2/1,2"));
+        assert!(rejects("4,3/3,3"));
+        // 行の柱の数や行の数の違い
+        assert!(rejects("3,3,3/3,3"));
+        assert!(rejects("3/3"));
+        assert!(rejects("3,3"));
+        assert!(rejects("3,x/3,3"));
+        // 毒ブロック（原点）がない
+        assert!(rejects("0,0/0,0"));
+        assert!(rejects("cells:"));
+        let poset = poset_board();
+        assert!(matches!(poset.parse_position::<u128>("cells:b;c"), Err(Error::InvalidPosition(_))));
+        assert!(matches!(poset.parse_position::<u128>("cells:a;c"), Err(Error::InvalidPosition(_))));
+        // 一般の半順序集合は高さ行列では指定できない
+        assert!(matches!(poset.parse_position::<u128>("1"), Err(Error::InvalidShape(_))));
+    }
+
+    #[test]
+    fn carried_over_results_match_the_larger_board() {
+        // 最後の軸が伸びる場合（インデックスはそのまま）と、前の軸が伸びる場合（移し替える）
+        for (sub, dims) in [(vec![2, 3, 3], vec![2, 3, 4]), (vec![2, 3, 3], vec![3, 3, 3])] {
+            let (sub, board) = (Board::new(sub).unwrap(), Board::new(dims).unwrap());
+            let memo: DashMap<u128, bool> = DashMap::new();
+            win(&sub, sub.full_state(), &memo);
+            let carried = DashMap::new();
+            assert_eq!(board.carry_over(&sub, &memo, &carried), memo.len() as u64);
+            let fresh = DashMap::new();
+            for entry in carried.iter() {
+                board.check_position(*entry.key()).unwrap();
+                assert_eq!(win(&board, *entry.key(), &fresh), *entry.value());
+            }
+        }
+    }
+
+    #[test]
+    fn boards_that_do_not_fit_carry_nothing_over() {
+        let (sub, board) = (Board::new(vec![3, 3]).unwrap(), Board::new(vec![2, 4]).unwrap());
+        let memo: DashMap<u128, bool> = DashMap::new();
+        win(&sub, sub.full_state(), &memo);
+        let carried: DashMap<u128, bool> = DashMap::new();
+        assert_eq!(board.carry_over(&sub, &memo, &carried), 0);
+        assert!(carried.is_empty());
+    }
+
+    #[test]
+    fn carried_over_tables_finish_by_retrograde() {
+        // 引き継いだ局面を除いて後退解析しても、一から解いた表と同じになる
+        let (sub, board) = (Board::new(vec![2, 3, 3]).unwrap(), Board::new(vec![2, 3, 4]).unwrap());
+        let sub_table = retrograde::<u128>(&sub, Ranker::new(&sub).unwrap());
+        let table = DenseMemo::new(Ranker::new(&board).unwrap());
+        assert!(board.carry_over::<u128>(&sub, &sub_table, &table) > 0);
+        retrograde_into::<u128>(&board, &table, None);
+        let fresh = retrograde::<u128>(&board, Ranker::new(&board).unwrap());
+        for r in 0..fresh.ranker().count() {
+            let state: u128 = fresh.ranker().unrank(r);
+            assert_eq!(table.get(&state), fresh.get(&state));
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use chomp::render::{Charset, RenderStyle};
+use chomp::svg::drawable_dims;
+use chomp::{Board, Error, Parallelism, PoisonMode, Ruleset};
+
+use crate::output::Format;
+
+pub const USAGE: &str = "使い方: chomp-rust <コマンド> <盤面> [オプション]
+コマンド:
+  solve <盤面>    勝敗と必勝手を求める
+  moves <盤面>    すべての合法手と、指した後の勝敗を一覧する
+  play <盤面>     エンジンと対局する
+  sweep <範囲> ...  各辺の長さを範囲で変えながら解き、1 つの大きさを 1 行に表示する（例: 2x3x1..19）
+  export <盤面> --output <ファイル>  解いた結果を書き出す（拡張子 .svg なら図、.db なら解のデータベース、
+                 .ptable と .txt ならすべての P 局面の表をバイナリとテキストで）
+  verify <盤面>   再帰的な探索と後退解析（と --db のデータベース）の結果が一致するか確かめる
+  grundy <盤面>   Grundy 値とその分布を求める
+  sum <盤面> <盤面> ...  盤面の直和を解く
+盤面は各辺の長さ（AxBx...）、Hasse 図のファイル（poset:<ファイル>）、
+または n の約数の盤面（div:<n>、約数 Chomp）で指定する
+  例: chomp-rust solve 2x3x19, chomp-rust solve 2x2x2x5 --memo dense, chomp-rust grundy 3x4,
+      chomp-rust sum 2x3x4 3x4 5, chomp-rust sweep 2x3x1..10, chomp-rust export 2x3x4 --output 2x3x4.svg
+規約:
+  --rules poison  毒ブロック（既定では原点）は選べず、毒ブロックだけが残ったら負け（既定）
+  --rules normal  毒ブロックなしで、最後のブロックを取った側が勝つ
+  --rules misere  毒ブロックなしで、最後のブロックを取った側が負ける（grundy では misère Grundy 値）
+  --poison <x,y,...>   毒ブロックの座標か要素の名前（繰り返し指定可。規約の既定の毒ブロックを置き換える）
+  --poison-mode forbid 毒を食べる手は指せない（既定）
+  --poison-mode lose   毒を食べる手も指せるが、指した側がその場で負ける
+  --forbid <x,y,...>   選べないブロックの座標か要素の名前（繰り返し指定可）
+局面:
+  --position <局面>    初期局面（既定は全ブロック）。高さ行列 \"2,2/2,1\" か、
+                       残っているブロックの並び \"cells:0,0;1,0;0,1\"
+  --position-file <ファイル>  初期局面をファイルから読む（書式は --position と同じで、改行でも区切れる）
+解き方:
+  --memo hash   局面を DashMap でメモ化する（既定）
+  --memo dense  局面の順位で引くビット列でメモ化する（1 局面 2 ビット）
+  --memo bounded  使うメモリの上限を決めた置換表でメモ化する。一杯になると局面を捨てて解き直す
+  --memo-size <バイト数>  --memo bounded の表の大きさ（K, M, G の接尾辞を使える。既定は 1G）
+  --solver recursive   初期局面から再帰的に探索する（既定）
+  --solver retrograde  すべての局面をブロック数の少ない順に後退解析する（表は常に dense）
+  --split-depth <手数>  初期局面からこの手数以内の局面でだけ手を並列に調べる（既定は制限なし）
+  --split-min-cells <個数>  ブロックがこの個数以上残っている局面でだけ手を並列に調べる
+  --threads <数>   計算に使うスレッド数（既定は CPU の数）
+  --progress <秒>  探索の進み具合を指定した間隔（0 より大きい秒数）で標準エラー出力に表示する
+  --db <ファイル>   解のデータベースを使う。あれば読み込んでから解き、途中経過と結果を書き出す
+                   （verify ではデータベースの内容を確かめる）
+  --checkpoint <秒>  --db の途中経過を書き出す間隔（既定は 60 秒）
+出力:
+  --render layers   solve と play で局面を最後の軸で切った層ごとに図示する（箱の盤面のみ）
+  --render heights  solve と play で局面を柱の高さの格子で図示する
+  --charset unicode|ascii  図に使う文字（既定は unicode）
+  --svg <ファイル>  solve で局面と必勝手を等角投影の SVG に書き出す（3 次元以下の箱の盤面のみ）
+  --output <ファイル>  export の書き出し先
+  --format text|json|csv  solve, moves, sweep の結果の出力形式（既定は text。json と csv では
+                   盤面・規約・局面・solver の版を含む決まった項目を出力し、途中の表示は標準エラー出力に回す）
+  --first human   play で人間が先手（既定）
+  --first engine  play でエンジンが先手
+コマンドが使わないオプション（moves での --db など）を指定するとエラーになる";
+
+/// メモ化に使う表の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoKind {
+    Hash,
+    Dense,
+    Bounded,
+}
+
+/// 勝敗の求め方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolverKind {
+    Recursive,
+    Retrograde,
+}
+
+/// 実行するコマンド
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// 勝敗と必勝手を求める
+    Solve,
+    /// Grundy 値とその分布を求める
+    Grundy,
+    /// 複数の盤面の直和を解く
+    Sum,
+    /// エンジンと対局する
+    Play,
+    /// すべての合法手と指した後の勝敗を一覧する
+    Moves,
+    /// 大きさを変えながら解く
+    Sweep,
+    /// 解いた結果をファイルに書き出す
+    Export,
+    /// 解き方の違いで結果が変わらないか確かめる
+    Verify,
+}
+
+/// コマンドラインで指定された設定
+pub struct Options {
+    pub command: Command,
+    /// sum と sweep では 1 つ以上、それ以外のコマンドでは 1 つ
+    pub boards: Vec<Board>,
+    pub memo: MemoKind,
+    /// --memo bounded の表のバイト数
+    pub memo_size: u64,
+    pub solver: SolverKind,
+    /// 初期局面の指定（盤面ごとに解釈する）。None なら全ブロック
+    pub position: Option<String>,
+    /// play でエンジンが先手か
+    pub engine_first: bool,
+    /// 局面を図示するときの描き方
+    pub render: Option<RenderStyle>,
+    pub charset: Charset,
+    /// solve で SVG を書き出すファイル
+    pub svg: Option<String>,
+    pub parallelism: Parallelism,
+    /// 計算に使うスレッド数
+    pub threads: Option<usize>,
+    /// 進み具合を表示する間隔
+    pub progress: Option<Duration>,
+    /// 解のデータベースのファイル
+    pub db: Option<String>,
+    /// データベースに途中経過を書き出す間隔（秒）
+    pub checkpoint: u64,
+    /// export の書き出し先
+    pub output: Option<String>,
+    /// solve, moves, sweep の結果の出力形式
+    pub format: Format,
+}
+
+/// コマンドライン引数を解釈する
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let (command, rest) = match args {
+        [cmd, rest @ ..] if cmd == "solve" => (Command::Solve, rest),
+        [cmd, rest @ ..] if cmd == "grundy" => (Command::Grundy, rest),
+        [cmd, rest @ ..] if cmd == "sum" => (Command::Sum, rest),
+        [cmd, rest @ ..] if cmd == "play" => (Command::Play, rest),
+        [cmd, rest @ ..] if cmd == "moves" => (Command::Moves, rest),
+        [cmd, rest @ ..] if cmd == "sweep" => (Command::Sweep, rest),
+        [cmd, rest @ ..] if cmd == "export" => (Command::Export, rest),
+        [cmd, rest @ ..] if cmd == "verify" => (Command::Verify, rest),
+        _ => return Err(USAGE.to_string()),
+    };
+    // 先頭から "--" で始まらない引数を盤面サイズとして読む
+    let shapes = rest.iter().take_while(|a| !a.starts_with("--")).count();
+    let (shapes, mut rest) = rest.split_at(shapes);
+    // sweep では "2x3x1..19" のような範囲を、範囲内のすべての大きさに展開する
+    let shapes: Vec<String> = if command == Command::Sweep {
+        shapes.iter().map(|s| expand_ranges(s)).collect::<Result<Vec<_>, _>>()?.concat()
+    } else {
+        shapes.to_vec()
+    };
+    let boards: Vec<Board> = shapes
+        .iter()
+        .map(|shape| Board::parse(shape))
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let many = matches!(command, Command::Sum | Command::Sweep);
+    if boards.is_empty() || (!many && boards.len() != 1) {
+        return Err(USAGE.to_string());
+    }
+    let mut options = Options { command, boards, memo: MemoKind::Hash,
+        memo_size: 1 << 30, solver: SolverKind::Recursive, position: None, engine_first: false,
+        render: None,
+        charset: Charset::default(),
+        svg: None,
+        parallelism: Parallelism::default(),
+        threads: None,
+        progress: None,
+        db: None,
+        checkpoint: 60,
+        output: None,
+        format: Format::default(),
+    };
+    let mut ruleset = Ruleset::default();
+    let mut poison_mode = PoisonMode::default();
+    let mut poison: Option<Vec<&str>> = None;
+    let mut forbidden = Vec::new();
+    let parse_err = |e: Error| e.to_string();
+    while let [flag, value, tail @ ..] = rest {
+        match (flag.as_str(), value.as_str()) {
+            ("--memo", "hash") => options.memo = MemoKind::Hash,
+            ("--memo", "dense") => options.memo = MemoKind::Dense,
+            ("--memo", "bounded") => options.memo = MemoKind::Bounded,
+            ("--memo-size", v) => options.memo_size = parse_bytes(v).ok_or_else(|| USAGE.to_string())?,
+            ("--solver", "recursive") => options.solver = SolverKind::Recursive,
+            ("--solver", "retrograde") => options.solver = SolverKind::Retrograde,
+            ("--rules", v) => ruleset = v.parse().map_err(parse_err)?,
+            ("--poison-mode", v) => poison_mode = v.parse().map_err(parse_err)?,
+            ("--poison", v) => poison.get_or_insert_with(Vec::new).push(v),
+            ("--forbid", v) => forbidden.push(v),
+            ("--render", v) => options.render = Some(v.parse().map_err(parse_err)?),
+            ("--charset", v) => options.charset = v.parse().map_err(parse_err)?,
+            ("--svg", v) => options.svg = Some(v.to_string()),
+            ("--split-depth", v) => options.parallelism.split_depth = v.parse().map_err(|_| USAGE.to_string())?,
+            ("--split-min-cells", v) => options.parallelism.min_cells = v.parse().map_err(|_| USAGE.to_string())?,
+            ("--threads", v) => match v.parse() {
+                Ok(n) if n > 0 => options.threads = Some(n),
+                _ => return Err(USAGE.to_string()),
+            },
+            ("--progress", v) => {
+                // 0 秒以下の間隔では表示のスレッドが休まず回り続けるので断る
+                let secs: f64 = v.parse().map_err(|_| USAGE.to_string())?;
+                match Duration::try_from_secs_f64(secs) {
+                    Ok(interval) if !interval.is_zero() => options.progress = Some(interval),
+                    _ => return Err(USAGE.to_string()),
+                }
+            }
+            ("--db", v) => options.db = Some(v.to_string()),
+            ("--checkpoint", v) => options.checkpoint = v.parse().map_err(|_| USAGE.to_string())?,
+            ("--output", v) => options.output = Some(v.to_string()),
+            ("--format", v) => options.format = v.parse().map_err(parse_err)?,
+            ("--first", "human") => options.engine_first = false,
+            ("--first", "engine") => options.engine_first = true,
+            ("--position", v) => options.position = Some(v.to_string()),
+            ("--position-file", v) => {
+                let text = fs::read_to_string(v).map_err(|e| format!("{} を読めません: {}", v, e))?;
+                options.position = Some(text);
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+        if !accepts(command, flag) {
+            return Err(format!("{} は {} では使えません\n{}", flag, args[0], USAGE));
+        }
+        rest = tail;
+    }
+    if !rest.is_empty()
+        || ((command == Command::Export) != options.output.is_some())
+        || (options.format != Format::Text && options.render.is_some())
+    {
+        return Err(USAGE.to_string());
+    }
+    // 規約を設定すると毒ブロックが既定の配置に戻るので、規約を先に反映する。
+    // ブロックの指定は盤面ごとに（箱なら座標、半順序集合なら要素の名前として）解釈する
+    options.boards = options
+        .boards
+        .into_iter()
+        .map(|b| {
+            let parse_cells = |cells: &[&str]| cells.iter().map(|c| b.parse_cell(c)).collect::<Result<Vec<_>, _>>();
+            let forbidden = parse_cells(&forbidden)?;
+            let poison = poison.as_deref().map(parse_cells).transpose()?;
+            let mut b = b.with_ruleset(ruleset).with_poison_mode(poison_mode).with_forbidden(forbidden);
+            if let Some(poison) = poison {
+                b = b.with_poison(poison);
+            }
+            Ok(b)
+        })
+        .collect::<Result<_, Error>>()
+        .map_err(parse_err)?;
+    // SVG に描けない盤面は解き始める前に断る
+    let svg_output = options.output.as_deref().is_some_and(|path| Path::new(path).extension() == Some("svg".as_ref()));
+    if options.svg.is_some() || svg_output {
+        options.boards.iter().try_for_each(|b| drawable_dims(b).map(|_| ())).map_err(parse_err)?;
+    }
+    Ok(options)
+}
+
+/// コマンドがオプション flag を使うか。使わないオプションは黙って無視せず、指定されたらエラーにする
+fn accepts(command: Command, flag: &str) -> bool {
+    use Command::*;
+    match flag {
+        "--rules" | "--poison" | "--poison-mode" | "--forbid" | "--threads" => true,
+        "--position" | "--position-file" => !matches!(command, Sum | Sweep),
+        "--memo" | "--memo-size" | "--solver" | "--progress" | "--checkpoint" | "--svg" => command == Solve,
+        "--db" => matches!(command, Solve | Verify),
+        "--split-depth" | "--split-min-cells" => matches!(command, Solve | Moves | Play | Sweep | Export | Verify),
+        "--render" | "--charset" => matches!(command, Solve | Play),
+        "--format" => matches!(command, Solve | Moves | Sweep),
+        "--output" => command == Export,
+        "--first" => command == Play,
+        _ => false,
+    }
+}
+
+/// 各辺の長さに "1..19" のような範囲（両端を含む）を含む盤面の指定を、範囲内のすべての大きさの
+/// 指定に展開する。前の軸ほどゆっくり変わる順に並べる。範囲を含まない指定はそのまま返す
+fn expand_ranges(spec: &str) -> Result<Vec<String>, String> {
+    if !spec.contains("..") {
+        return Ok(vec![spec.to_string()]);
+    }
+    let mut shapes = vec![Vec::new()];
+    for axis in spec.split('x') {
+        let (lo, hi) = match axis.split_once("..") {
+            Some((lo, hi)) => (lo.trim().parse::<u32>(), hi.trim().parse::<u32>()),
+            None => (axis.trim().parse(), axis.trim().parse()),
+        };
+        let (Ok(lo), Ok(hi)) = (lo, hi) else {
+            return Err(format!("盤面サイズの範囲を解釈できません: {}", spec));
+        };
+        shapes = shapes
+            .into_iter()
+            .flat_map(|shape: Vec<u32>| {
+                (lo..=hi).map(move |d| {
+                    let mut shape = shape.clone();
+                    shape.push(d);
+                    shape
+                })
+            })
+            .collect();
+    }
+    Ok(shapes
+        .into_iter()
+        .map(|shape| shape.iter().map(u32::to_string).collect::<Vec<_>>().join("x"))
+        .collect())
+}
+
+/// "512M" のようなバイト数を解釈する。接尾辞 K, M, G はそれぞれ 2^10, 2^20, 2^30 倍
+fn parse_bytes(s: &str) -> Option<u64> {
+    let (digits, shift) = match s.as_bytes().last()? {
+        b'K' | b'k' => (&s[..s.len() - 1], 10),
+        b'M' | b'm' => (&s[..s.len() - 1], 20),
+        b'G' | b'g' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Options, String> {
+        parse_args(&line.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn expands_ranges_with_earlier_axes_slowest() {
+        assert_eq!(expand_ranges("2x3x4").unwrap(), ["2x3x4"]);
+        assert_eq!(expand_ranges("2x3x1..3").unwrap(), ["2x3x1", "2x3x2", "2x3x3"]);
+        assert_eq!(expand_ranges("1..2x2..3").unwrap(), ["1x2", "1x3", "2x2", "2x3"]);
+        assert!(expand_ranges("2x3..1").unwrap().is_empty());
+        assert!(expand_ranges("2xa..3").is_err());
+        assert!(expand_ranges("2x1..").is_err());
+    }
+
+    #[test]
+    fn parses_byte_sizes() {
+        assert_eq!(parse_bytes("1000"), Some(1000));
+        assert_eq!(parse_bytes("512K"), Some(512 << 10));
+        assert_eq!(parse_bytes("3m"), Some(3 << 20));
+        assert_eq!(parse_bytes("2G"), Some(2 << 30));
+        assert_eq!(parse_bytes(""), None);
+        assert_eq!(parse_bytes("G"), None);
+        assert_eq!(parse_bytes("1.5G"), None);
+        assert_eq!(parse_bytes("-1K"), None);
+        assert_eq!(parse_bytes("17179869184G"), None);
+    }
+
+    #[test]
+    fn parses_commands_and_options() {
+        let options = parse("solve 2x3 --memo dense --solver retrograde --progress 0.5 --db a.db --split-depth 4").unwrap();
+        assert_eq!(options.command, Command::Solve);
+        assert_eq!(options.boards.len(), 1);
+        assert_eq!((options.memo, options.solver), (MemoKind::Dense, SolverKind::Retrograde));
+        assert_eq!(options.progress, Some(Duration::from_millis(500)));
+        assert_eq!(options.db.as_deref(), Some("a.db"));
+        assert_eq!(options.parallelism.split_depth, 4);
+        assert_eq!(parse("solve 2x3 --memo-size 64M").unwrap().memo_size, 64 << 20);
+
+        let sweep = parse("sweep 2x1..3 3 --format csv").unwrap();
+        assert_eq!(sweep.boards.iter().map(|b| b.to_string()).collect::<Vec<_>>(), ["2x1", "2x2", "2x3", "3"]);
+        assert_eq!(sweep.format, Format::Csv);
+
+        let rules = parse("moves 3x3 --rules misere --forbid 1,1").unwrap();
+        assert_eq!(rules.boards[0].ruleset(), Ruleset::Misere);
+        assert_eq!(rules.boards[0].forbidden(), [4]);
+        assert!(parse("verify 2x3 --db a.db --split-min-cells 3").is_ok());
+        assert!(parse("play 2x3 --first engine --render heights --split-depth 2").unwrap().engine_first);
+        assert!(parse("export 2x3 --output a.db").is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for line in [
+            "",
+            "solve",
+            "solve 2x3 3x4",
+            "unknown 2x3",
+            "solve 2x3 --memo",
+            "solve 2x3 --memo none",
+            "solve 2x3 --progress 0",
+            "solve 2x3 --progress -1",
+            "solve 2x3 --threads 0",
+            "export 2x3",
+            "solve 2x3 --output a.db",
+            "solve 2x3 --format json --render layers",
+            "sum 2x3 3x4 --position 1,1",
+            "solve 2x2x2x2 --svg a.svg",
+            "export 2x2x2x2 --output a.svg",
+        ] {
+            assert!(parse(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_options_the_command_ignores() {
+        for line in [
+            "moves 2x3 --db a.db",
+            "sweep 2x1..3 --memo dense",
+            "export 2x3 --output a.db --solver retrograde",
+            "verify 2x3 --progress 1",
+            "grundy 2x3 --svg a.svg",
+            "play 2x3 --checkpoint 10",
+            "grundy 2x3 --split-depth 2",
+            "sum 2x3 3 --split-min-cells 2",
+            "moves 2x3 --render layers",
+            "grundy 2x3 --format json",
+            "solve 2x3 --first engine",
+        ] {
+            let err = parse(line).err().unwrap_or_else(|| panic!("{}", line));
+            assert!(err.contains("では使えません"), "{}: {}", line, err);
+        }
+    }
+}
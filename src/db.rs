@@ -0,0 +1,364 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::error::Error;
+use crate::memo::Memo;
+use crate::state::State;
+
+/// ファイルの先頭に置く識別子
+const MAGIC: &[u8; 8] = b"CHOMPDB\0";
+/// 形式の版。形式を変えたら上げる
+const VERSION: u32 = 2;
+
+/// 解のデータベースのファイル形式（整数はすべてリトルエンディアン）:
+///   MAGIC (8 バイト), VERSION (u32),
+///   盤面の鍵の長さ (u32), 盤面の鍵 (UTF-8),
+///   ブロック数 tot (u32), 局面数 (u64),
+///   局面ごとに: 状態 (ceil(tot / 8) バイト。ブロック i がバイト i / 8 の第 i % 8 ビット), 勝敗 (1 バイト, 1 = 勝ち)
+///
+/// 盤面の鍵はブロックの順序（名前と上集合の指紋）と、勝敗に影響する規約・毒ブロック・選べないブロックを
+/// まとめた文字列で、鍵の違うデータベースは読み込まない。盤面の名前（poset: ならファイルのパス）は含めないので、
+/// 同じ半順序集合なら別の場所のファイルから読んでも同じ鍵になり、ファイルを書き換えれば別の鍵になる
+pub fn board_key(board: &Board) -> String {
+    let names = |cells: &[u32]| cells.iter().map(|&i| board.cell_name(i)).collect::<Vec<_>>().join(";");
+    format!(
+        "order={:016x} tot={} rules={} poison-mode={} poison={} forbid={}",
+        board.poset().fingerprint(),
+        board.tot(),
+        board.ruleset(),
+        board.poison_mode(),
+        names(board.poison()),
+        names(board.forbidden())
+    )
+}
+
+/// memo のすべての局面を path に書き出し、書き出した局面数を返す。
+/// 途中で止まっても前のファイルが壊れないよう、一時ファイルに書いてから置き換える
+pub fn save<S: State, M: Memo<S>>(path: &str, board: &Board, memo: &M) -> Result<u64, Error> {
+    let mut entries = Vec::new();
+    memo.for_each_entry(&mut |state, win| entries.push((state, win)));
+    write_atomically(path, |out| {
+        write_header(out, board, MAGIC, VERSION)?;
+        out.write_all(&(entries.len() as u64).to_le_bytes())?;
+        let mut record = vec![0u8; state_len(board) + 1];
+        for &(state, win) in &entries {
+            encode_state(board, state, &mut record);
+            *record.last_mut().unwrap() = win as u8;
+            out.write_all(&record)?;
+        }
+        Ok(())
+    })?;
+    Ok(entries.len() as u64)
+}
+
+/// path のデータベースの局面を memo に読み込み、読み込んだ局面数を返す。
+/// 版や盤面の鍵が合わないか、盤面の局面として正しくない状態があればエラー
+pub fn load<S: State, M: Memo<S>>(path: &str, board: &Board, memo: &M) -> Result<u64, Error> {
+    let mut input = open(path)?;
+    read_header(&mut input, path, board, MAGIC, VERSION, "解のデータベース")?;
+    let count = read_u64(&mut input, path)?;
+    let len = state_len(board);
+    for _ in 0..count {
+        let record = read_bytes(&mut input, path, len + 1)?;
+        let state = decode_state(board, &record);
+        board.check_position(state)?;
+        memo.insert(state, record[len] != 0);
+    }
+    Ok(count)
+}
+
+/// 一時ファイルに write で書いてから path に置き換える。途中で止まっても前のファイルは壊れない
+pub(crate) fn write_atomically(
+    path: &str,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> Result<(), Error> {
+    let io_err = |e: std::io::Error| Error::Io(format!("{} に書き込めません: {}", path, e));
+    let tmp = format!("{}.tmp", path);
+    let mut out = BufWriter::new(File::create(&tmp).map_err(io_err)?);
+    write(&mut out).map_err(io_err)?;
+    out.flush().map_err(io_err)?;
+    drop(out);
+    fs::rename(&tmp, path).map_err(io_err)
+}
+
+/// 見出し（識別子 magic, 版 version (u32), 盤面の鍵の長さ (u32), 盤面の鍵, ブロック数 (u32)）を書く
+pub(crate) fn write_header(out: &mut impl Write, board: &Board, magic: &[u8; 8], version: u32) -> std::io::Result<()> {
+    let key = board_key(board);
+    out.write_all(magic)?;
+    out.write_all(&version.to_le_bytes())?;
+    out.write_all(&(key.len() as u32).to_le_bytes())?;
+    out.write_all(key.as_bytes())?;
+    out.write_all(&board.tot().to_le_bytes())
+}
+
+/// path を読むために開く
+pub(crate) fn open(path: &str) -> Result<BufReader<File>, Error> {
+    File::open(path).map(BufReader::new).map_err(|e| Error::Io(format!("{} を読めません: {}", path, e)))
+}
+
+/// write_header の見出しを読み、board のものか確かめる。識別子が違えば path は what ではないというエラー
+pub(crate) fn read_header(
+    input: &mut impl Read,
+    path: &str,
+    board: &Board,
+    magic: &[u8; 8],
+    version: u32,
+    what: &str,
+) -> Result<(), Error> {
+    if read_bytes(input, path, magic.len())? != magic {
+        return Err(Error::InvalidDatabase(format!("{} は{}ではありません", path, what)));
+    }
+    let found = read_u32(input, path)?;
+    if found != version {
+        return Err(Error::InvalidDatabase(format!(
+            "{} の版 {} には対応していません（対応している版は {}）",
+            path, found, version
+        )));
+    }
+    let key_len = read_u32(input, path)? as usize;
+    let key = String::from_utf8_lossy(&read_bytes(input, path, key_len)?).into_owned();
+    if key != board_key(board) {
+        return Err(Error::InvalidDatabase(format!("{} は別の盤面のものです: {}", path, key)));
+    }
+    let tot = read_u32(input, path)?;
+    if tot != board.tot() {
+        return Err(Error::InvalidDatabase(format!("{} のブロック数 {} が盤面と一致しません", path, tot)));
+    }
+    Ok(())
+}
+
+/// len バイトを読む
+pub(crate) fn read_bytes(input: &mut impl Read, path: &str, len: usize) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0u8; len];
+    input.read_exact(&mut buf).map_err(|e| Error::Io(format!("{} を読めません: {}", path, e)))?;
+    Ok(buf)
+}
+
+fn read_u32(input: &mut impl Read, path: &str) -> Result<u32, Error> {
+    Ok(u32::from_le_bytes(read_bytes(input, path, 4)?.try_into().unwrap()))
+}
+
+pub(crate) fn read_u64(input: &mut impl Read, path: &str) -> Result<u64, Error> {
+    Ok(u64::from_le_bytes(read_bytes(input, path, 8)?.try_into().unwrap()))
+}
+
+/// 状態 1 つを書き出すバイト数
+pub(crate) fn state_len(board: &Board) -> usize {
+    board.tot().div_ceil(8) as usize
+}
+
+/// record の先頭 state_len バイトのビット列から状態を読む（encode_state の逆）
+pub(crate) fn decode_state<S: State>(board: &Board, record: &[u8]) -> S {
+    (0..board.tot())
+        .filter(|&i| record[(i / 8) as usize] >> (i % 8) & 1 != 0)
+        .fold(S::zero(), |state, i| state | S::bit(i))
+}
+
+/// 状態を record の先頭 state_len バイトにビット列として書く（ブロック i がバイト i / 8 の第 i % 8 ビット）
+pub(crate) fn encode_state<S: State>(board: &Board, state: S, record: &mut [u8]) {
+    record[..state_len(board)].fill(0);
+    for i in (0..board.tot()).filter(|&i| state.has(i)) {
+        record[(i / 8) as usize] |= 1 << (i % 8);
+    }
+}
+
+/// 時刻を確かめる間隔（表への書き込み回数）
+const CHECK_EVERY: u64 = 4096;
+
+/// 書き出すたびに、書き出した局面数かエラーを受け取る関数
+pub type SaveReport<'a> = Box<dyn Fn(Result<u64, Error>) + Sync + 'a>;
+
+/// 表への書き込みを数え、一定時間ごとに表全体をデータベースに書き出す表のラッパー。
+/// win が表に書くのは解き終えた局面だけなので、書き出した時点の表から再開しても結果は変わらない
+pub struct Checkpoint<'a, S, M> {
+    memo: &'a M,
+    board: &'a Board,
+    path: &'a str,
+    interval: Duration,
+    /// 最後に書き出した時刻。書き出し中は他のスレッドが書き出さないようロックを持つ
+    last: Mutex<Instant>,
+    /// 表への書き込み回数
+    inserts: AtomicU64,
+    /// これまでに書き出した回数
+    saved: AtomicU64,
+    /// 書き出した結果の知らせ先
+    report: Option<SaveReport<'a>>,
+    _state: PhantomData<fn(S)>,
+}
+
+impl<'a, S: State, M: Memo<S>> Checkpoint<'a, S, M> {
+    pub fn new(memo: &'a M, board: &'a Board, path: &'a str, interval: Duration) -> Checkpoint<'a, S, M> {
+        Checkpoint {
+            memo,
+            board,
+            path,
+            interval,
+            last: Mutex::new(Instant::now()),
+            inserts: AtomicU64::new(0),
+            saved: AtomicU64::new(0),
+            report: None,
+            _state: PhantomData,
+        }
+    }
+
+    /// 書き出すたびに、書き出した局面数か書き出せなかったエラーを report に渡す
+    pub fn with_report(mut self, report: impl Fn(Result<u64, Error>) + Sync + 'a) -> Checkpoint<'a, S, M> {
+        self.report = Some(Box::new(report));
+        self
+    }
+
+    /// これまでにチェックポイントを書き出した回数
+    pub fn saved(&self) -> u64 {
+        self.saved.load(Ordering::Relaxed)
+    }
+
+    /// 書き込み CHECK_EVERY 回ごとに時刻を確かめ、前回から interval 以上たっていれば表を書き出す。
+    /// 他のスレッドが書き出し中なら何もしない
+    fn maybe_save(&self) {
+        if !(self.inserts.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(CHECK_EVERY) {
+            return;
+        }
+        let Ok(mut last) = self.last.try_lock() else {
+            return;
+        };
+        if last.elapsed() < self.interval {
+            return;
+        }
+        // 書き出しに失敗しても探索は続けられるので、知らせるだけにする
+        let result = save(self.path, self.board, self.memo);
+        if result.is_ok() {
+            self.saved.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(report) = &self.report {
+            report(result);
+        }
+        *last = Instant::now();
+    }
+}
+
+impl<S: State, M: Memo<S>> Memo<S> for Checkpoint<'_, S, M> {
+    fn get(&self, state: &S) -> Option<bool> {
+        self.memo.get(state)
+    }
+
+    fn insert(&self, state: S, win: bool) {
+        self.memo.insert(state, win);
+        self.maybe_save();
+    }
+
+    fn for_each_entry(&self, f: &mut dyn FnMut(S, bool)) {
+        self.memo.for_each_entry(f)
+    }
+
+    fn entries(&self) -> u64 {
+        self.memo.entries()
+    }
+
+    fn approx_bytes(&self) -> u64 {
+        self.memo.approx_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank::Ranker;
+    use crate::solver::{retrograde, win};
+    use dashmap::DashMap;
+
+    /// テストごとに別の一時ファイルの名前
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("chomp-db-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn retrograde_table_round_trips() {
+        let board = Board::new(vec![2, 2, 3]).unwrap();
+        let table = retrograde::<u128>(&board, Ranker::new(&board).unwrap());
+        let ranker = table.ranker();
+        let path = temp_path("retrograde.db");
+        // 空の盤面は毒ブロックを含まないので局面ではなく、表に入っていない
+        assert_eq!(save::<u128, _>(&path, &board, &table).unwrap(), ranker.count() - 1);
+        let loaded: DashMap<u128, bool> = DashMap::new();
+        assert_eq!(load(&path, &board, &loaded).unwrap(), ranker.count() - 1);
+        for entry in loaded.iter() {
+            assert_eq!(table.get(entry.key()), Some(*entry.value()));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recursive_table_round_trips() {
+        let board = Board::new(vec![2, 3, 4]).unwrap();
+        let memo: DashMap<u128, bool> = DashMap::new();
+        assert!(win(&board, board.full_state(), &memo));
+        let path = temp_path("recursive.db");
+        assert_eq!(save(&path, &board, &memo).unwrap(), memo.len() as u64);
+        let loaded: DashMap<u128, bool> = DashMap::new();
+        load(&path, &board, &loaded).unwrap();
+        assert_eq!(loaded.len(), memo.len());
+        for entry in memo.iter() {
+            assert_eq!(loaded.get(entry.key()).map(|v| *v), Some(*entry.value()));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn other_boards_are_rejected() {
+        let board = Board::new(vec![2, 2, 3]).unwrap();
+        let memo: DashMap<u128, bool> = DashMap::new();
+        win(&board, board.full_state(), &memo);
+        let path = temp_path("other.db");
+        save(&path, &board, &memo).unwrap();
+        let other = Board::new(vec![3, 2, 2]).unwrap();
+        assert!(matches!(load(&path, &other, &DashMap::<u128, bool>::new()), Err(Error::InvalidDatabase(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn posets_are_keyed_by_their_order() {
+        let poset = |name: &str, text: &str| {
+            let path = temp_path(name);
+            fs::write(&path, text).unwrap();
+            let board = Board::parse(&format!("poset:{}", path)).unwrap();
+            fs::remove_file(&path).unwrap();
+            board
+        };
+        let board = poset("order.txt", "a b\nb c\na d\n");
+        let memo: DashMap<u128, bool> = DashMap::new();
+        win(&board, board.full_state(), &memo);
+        let path = temp_path("poset.db");
+        save(&path, &board, &memo).unwrap();
+        // 同じ順序なら別の場所のファイルから読んだ盤面でも読み込める
+        let moved = poset("moved.txt", "a b\nb c\na d\n");
+        assert_eq!(load(&path, &moved, &DashMap::<u128, bool>::new()).unwrap(), memo.len() as u64);
+        // 辺を書き換えた盤面は要素が同じでも別の盤面
+        let edited = poset("edited.txt", "a b\nb c\nb d\n");
+        assert_eq!(edited.tot(), board.tot());
+        assert!(matches!(load(&path, &edited, &DashMap::<u128, bool>::new()), Err(Error::InvalidDatabase(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checkpoints_report_each_save() {
+        let board = Board::new(vec![3, 3, 5]).unwrap();
+        let memo: DashMap<u128, bool> = DashMap::new();
+        let path = temp_path("checkpoint.db");
+        let reports = Mutex::new(Vec::new());
+        let checkpoint = Checkpoint::new(&memo, &board, &path, Duration::ZERO)
+            .with_report(|result| reports.lock().unwrap().push(result.unwrap()));
+        assert!(win(&board, board.full_state(), &checkpoint));
+        let saved = checkpoint.saved();
+        drop(checkpoint);
+        let reports = reports.into_inner().unwrap();
+        assert!(saved > 0);
+        assert_eq!(reports.len() as u64, saved);
+        assert!(reports.iter().all(|&count| count > 0 && count <= memo.len() as u64));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+use crate::board::Board;
+use crate::error::Error;
+
+/// 試し割りで探す素因数の上限。これを超える素因数は、残りが素数のとき（Miller–Rabin で判定）だけ扱える
+const TRIAL_LIMIT: u64 = 1 << 20;
+
+/// n を素因数分解し、(素数, 指数) の組を素数の小さい順に返す（n = 1 なら空）。
+/// TRIAL_LIMIT までの試し割りで割り切れない残りが素数でなければ（TRIAL_LIMIT を超える素因数を
+/// 2 つ以上持てば）エラー。残りが素数かは Miller–Rabin で判定するので、大きな素数でもすぐに終わる
+pub fn factorize(mut n: u64) -> Result<Vec<(u64, u32)>, Error> {
+    let mut factors = Vec::new();
+    let mut p = 2;
+    while p <= TRIAL_LIMIT && p <= n / p {
+        let mut e = 0;
+        while n.is_multiple_of(p) {
+            n /= p;
+            e += 1;
+        }
+        if e > 0 {
+            factors.push((p, e));
+        }
+        p += 1;
+    }
+    if n > 1 {
+        // 試し割りを最後まで終えたか、残りが素数なら、残りは 1 つの素因数
+        if p <= TRIAL_LIMIT || is_prime(n) {
+            factors.push((n, 1));
+        } else {
+            return Err(Error::InvalidShape(format!(
+                "約数 Chomp の n は {} を超える素因数を 2 つ以上持つと素因数分解できません（残りの因数 {}）",
+                TRIAL_LIMIT, n
+            )));
+        }
+    }
+    Ok(factors)
+}
+
+/// n が素数かを Miller–Rabin 法で判定する。u64 の範囲ではこの底で確定的に正しい
+fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    if let Some(&p) = BASES.iter().find(|&&p| n.is_multiple_of(p)) {
+        return n == p;
+    }
+    let mul = |a: u64, b: u64| (a as u128 * b as u128 % n as u128) as u64;
+    let pow = |mut a: u64, mut e: u64| {
+        let mut r = 1;
+        while e > 0 {
+            if e & 1 == 1 {
+                r = mul(r, a);
+            }
+            a = mul(a, a);
+            e >>= 1;
+        }
+        r
+    };
+    let (d, s) = ((n - 1) >> (n - 1).trailing_zeros(), (n - 1).trailing_zeros());
+    BASES.iter().all(|&a| {
+        let mut x = pow(a, d);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul(x, x);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// n の約数を整除関係で順序付けた盤面（約数 Chomp、Schuh のゲーム）を作る。
+/// n = p_1^e_1 ... p_k^e_k の約数 p_1^c_1 ... p_k^c_k を指数の組 (c_1, ..., c_k) と同一視すると、
+/// 整除関係は各辺 e_i + 1 の箱の直積順序になるので、箱の盤面をそのまま使う。
+/// ブロックの名前は約数そのもので、毒ブロックは 1。約数を選ぶとその倍数がすべて取り除かれる
+pub fn divisor_board(n: u64) -> Result<Board, Error> {
+    if n == 0 {
+        return Err(Error::InvalidShape("約数 Chomp の n は 1 以上である必要があります".to_string()));
+    }
+    let factors = factorize(n)?;
+    let dims: Vec<u32> = if factors.is_empty() {
+        vec![1]
+    } else {
+        factors.iter().map(|&(_, e)| e + 1).collect()
+    };
+    let board = Board::new(dims)?;
+    // 座標の各成分を指数として約数を求める
+    let labels = (0..board.tot())
+        .map(|i| {
+            let coord = board.index_to_coord(i).unwrap();
+            factors.iter().zip(&coord.0).map(|(&(p, _), &e)| p.pow(e)).product::<u64>().to_string()
+        })
+        .collect();
+    Ok(board.relabel(format!("div({})", n), labels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factorizes_small_and_large_numbers() {
+        assert_eq!(factorize(1).unwrap(), []);
+        assert_eq!(factorize(720).unwrap(), [(2, 4), (3, 2), (5, 1)]);
+        assert_eq!(factorize(1 << 63).unwrap(), [(2, 63)]);
+        // u64 の最大の素数と、その 2 倍は試し割りを打ち切っても分解できる
+        let p = 18_446_744_073_709_551_557;
+        assert_eq!(factorize(p).unwrap(), [(p, 1)]);
+        assert_eq!(factorize(2 * 4_294_967_311).unwrap(), [(2, 1), (4_294_967_311, 1)]);
+        // 大きな素因数を 2 つ持つ数は扱えない
+        assert!(matches!(factorize(1_048_583 * 1_048_589), Err(Error::InvalidShape(_))));
+    }
+
+    #[test]
+    fn labels_cells_with_divisors() {
+        let board = divisor_board(1).unwrap();
+        assert_eq!((board.tot(), board.cell_name(0)), (1, "1"));
+
+        // 素数の冪は 1 次元の盤面
+        let board = divisor_board(8).unwrap();
+        assert_eq!(board.dims(), Some(&[4][..]));
+        let names: Vec<&str> = (0..board.tot()).map(|i| board.cell_name(i)).collect();
+        assert_eq!(names, ["1", "2", "4", "8"]);
+
+        let board = divisor_board(720).unwrap();
+        assert_eq!(board.to_string(), "div(720)");
+        let mut divisors: Vec<u64> = (0..board.tot()).map(|i| board.cell_name(i).parse().unwrap()).collect();
+        divisors.sort_unstable();
+        assert_eq!(divisors, (1..=720).filter(|d| 720 % d == 0).collect::<Vec<u64>>());
+        assert_eq!(board.poison(), [board.parse_cell("1").unwrap()]);
+        // 360 を選ぶと 360 と 720 が取り除かれる
+        let mask: u128 = board.removal_mask(board.parse_cell("360").unwrap());
+        assert_eq!(mask.count_ones(), 2);
+        assert!(board.parse_cell("7").is_err());
+    }
+
+    #[test]
+    fn rejects_zero_and_too_many_divisors() {
+        assert!(matches!(divisor_board(0), Err(Error::InvalidShape(_))));
+        // 2^4 3^4 5^2 7^2 11 13 17 の約数は 5 * 5 * 3 * 3 * 2 * 2 * 2 = 1800 個
+        assert!(matches!(divisor_board(16 * 81 * 25 * 49 * 11 * 13 * 17), Err(Error::TooManyCells { .. })));
+        assert!(matches!(Board::parse("div:x"), Err(Error::InvalidShape(_))));
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+
+/// 盤面の指定や計算で起こりうるエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// 盤面サイズの指定が不正
+    InvalidShape(String),
+    /// 全ブロック数が状態型で表現できる上限を超えている
+    TooManyCells { cells: u64, capacity: u32 },
+    /// 局面が盤面の order ideal（下に閉じたブロック集合）になっていない
+    InvalidPosition(String),
+    /// 局面の数が順位付けで扱える範囲を超えている
+    TooManyPositions(String),
+    /// 規約の指定が不正
+    InvalidRules(String),
+    /// 半順序集合の指定が不正
+    InvalidPoset(String),
+    /// ファイルの読み書きに失敗した
+    Io(String),
+    /// 解のデータベースの形式や対象の盤面が合わない
+    InvalidDatabase(String),
+    /// 出力先や出力形式の指定が不正
+    InvalidOutput(String),
+    /// 解き方によって結果が食い違った
+    VerificationFailed(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidShape(msg) => write!(f, "{}", msg),
+            Error::TooManyCells { cells, capacity } => write!(
+                f,
+                "全ブロック数 {} は状態の表現できる上限 {} を超えています",
+                cells, capacity
+            ),
+            Error::InvalidPosition(msg) => write!(f, "不正な局面です: {}", msg),
+            Error::TooManyPositions(msg) => write!(f, "{}", msg),
+            Error::InvalidRules(msg) => write!(f, "{}", msg),
+            Error::InvalidPoset(msg) => write!(f, "不正な半順序集合です: {}", msg),
+            Error::Io(msg) => write!(f, "{}", msg),
+            Error::InvalidDatabase(msg) => write!(f, "不正なデータベースです: {}", msg),
+            Error::InvalidOutput(msg) => write!(f, "{}", msg),
+            Error::VerificationFailed(msg) => write!(f, "検証に失敗しました: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
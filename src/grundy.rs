@@ -0,0 +1,64 @@
+use dashmap::DashMap;
+use rayon::prelude::*;
+
+use crate::board::Board;
+use crate::state::State;
+
+/// 値の集合に含まれない最小の非負整数（minimum excludant）
+pub fn mex(values: impl IntoIterator<Item = u32>) -> u32 {
+    let mut values: Vec<u32> = values.into_iter().collect();
+    values.sort_unstable();
+    values.dedup();
+    values.iter().zip(0..).find(|&(&v, i)| v != i).map_or(values.len() as u32, |(_, i)| i)
+}
+
+/// 現在の状態 state の Sprague–Grundy 値（nim 値）を並列再帰的に求める関数。
+/// 合法手で移れる局面の Grundy 値の mex で、0 なら手番のプレイヤーの負け（P 局面）。
+/// memo は win と同じく DashMap で並列安全にメモ化します。
+pub fn grundy<S: State>(board: &Board, state: S, memo: &DashMap<S, u32>) -> u32 {
+    if let Some(res) = memo.get(&state) {
+        return *res;
+    }
+    let values: Vec<u32> = board
+        .legal_moves(state)
+        .par_iter()
+        .map(|&(_, new_state)| grundy(board, new_state, memo))
+        .collect();
+    // 終端状態（合法手がない）は空集合の mex で 0。毒を食べる手しか残っていなくても
+    // （PoisonMode::Loses）、手番のプレイヤーの負けなので 0 のままでよい
+    let value = mex(values);
+    memo.insert(state, value);
+    value
+}
+
+/// 現在の状態 state の misère Grundy 値を求める関数。合法手のない局面を 1（PoisonMode::Loses で
+/// 毒を食べる手しか残っていなければ、手番の負けなので 0）、それ以外を
+/// 移れる局面の値の mex とする（grundy とは終端の値だけが違う）。値が 0 であることと、
+/// 最後のブロックを取った側が負ける misère play で手番のプレイヤーが負けることが一致する。
+/// misère play の和は Grundy 値の排他的論理和では求まらないので、盤面単体の解析用
+pub fn misere_grundy<S: State>(board: &Board, state: S, memo: &DashMap<S, u32>) -> u32 {
+    if let Some(res) = memo.get(&state) {
+        return *res;
+    }
+    let values: Vec<u32> = board
+        .legal_moves(state)
+        .par_iter()
+        .map(|&(_, new_state)| misere_grundy(board, new_state, memo))
+        .collect();
+    let value = if values.is_empty() { board.wins_without_moves(state) as u32 } else { mex(values) };
+    memo.insert(state, value);
+    value
+}
+
+/// memo に記録されている局面の Grundy 値の分布。添字が Grundy 値、値がその局面数
+pub fn distribution<S: State>(memo: &DashMap<S, u32>) -> Vec<u64> {
+    let mut counts = Vec::new();
+    for entry in memo.iter() {
+        let v = *entry.value() as usize;
+        if counts.len() <= v {
+            counts.resize(v + 1, 0);
+        }
+        counts[v] += 1;
+    }
+    counts
+}
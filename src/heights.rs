@@ -0,0 +1,34 @@
+use std::fmt;
+
+/// 下に閉じた状態（order ideal）を、最後の軸方向に積まれた柱の高さの並びとして表したもの。
+/// 3 次元の盤面なら X×Y の格子上の高さ行列で、x, y のどちらの方向にも単調非増加な
+/// plane partition になる。柱は最後の軸以外の座標の混合基数インデックス順に並ぶ。
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Heights {
+    /// 格子の第 0 軸の長さ。表示で 1 行に並べる柱の数
+    row_len: u32,
+    heights: Vec<u32>,
+}
+
+impl Heights {
+    pub fn new(row_len: u32, heights: Vec<u32>) -> Heights {
+        Heights { row_len, heights }
+    }
+
+    /// 柱の高さ（混合基数インデックス順）
+    pub fn as_slice(&self) -> &[u32] {
+        &self.heights
+    }
+}
+
+/// 第 0 軸方向の柱を "," で、行を "/" で区切って表示する（例: 2x3x19 の初期状態は "19,19/19,19/19,19"）
+impl fmt::Display for Heights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<String> = self
+            .heights
+            .chunks(self.row_len as usize)
+            .map(|row| row.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+            .collect();
+        write!(f, "{}", rows.join("/"))
+    }
+}
@@ -0,0 +1,53 @@
+//! 多次元の箱や任意の有限半順序集合の上の Chomp を解くライブラリ。
+//!
+//! 局面はブロックの存在をビットで表した状態型（[`State`]。u128 や [`Bits`]）で表し、
+//! 盤面（[`Board`]）が合法手（[`Board::legal_moves`]）を与える。勝敗は [`win`] と
+//! [`winning_moves`] で求め、解いた局面は表（[`Memo`]。DashMap、[`DenseMemo`]、[`BoundedMemo`]）に覚える。
+//!
+//! ```
+//! use chomp::{win, winning_moves, Board};
+//! use dashmap::DashMap;
+//!
+//! let board = Board::new(vec![2, 3, 4]).unwrap();
+//! let state: u128 = board.full_state();
+//! let memo = DashMap::new();
+//! assert!(win(&board, state, &memo));
+//! let moves: Vec<&str> = winning_moves(&board, state, &memo).iter().map(|&mv| board.cell_name(mv)).collect();
+//! assert_eq!(moves, ["(0, 1, 1)"]);
+//! ```
+
+pub mod board;
+pub mod db;
+pub mod divisor;
+pub mod error;
+pub mod grundy;
+pub mod heights;
+pub mod memo;
+pub mod poset;
+pub mod progress;
+pub mod ptable;
+pub mod rank;
+pub mod render;
+pub mod rules;
+pub mod solver;
+pub mod state;
+pub mod sum;
+pub mod summary;
+pub mod svg;
+pub mod sweep;
+pub mod verify;
+
+pub use board::{Board, Coord};
+pub use error::Error;
+pub use heights::Heights;
+pub use memo::{BoundedMemo, DenseMemo, Memo};
+pub use poset::Poset;
+pub use rank::Ranker;
+pub use rules::{PoisonMode, Ruleset};
+pub use solver::{
+    engine_move, retrograde, retrograde_into, win, win_with, winning_moves, winning_moves_with, Parallelism,
+};
+pub use state::{Bits, State, MAX_CELLS};
+
+/// ライブラリの版。書き出す結果に、どの版の solver で求めたかとして記録する
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
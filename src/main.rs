@@ -1,105 +1,538 @@
-use std::sync::Arc;
+mod cli;
+mod output;
+mod play;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 use dashmap::DashMap;
-use rayon::prelude::*;
-
-// 盤面サイズ：x軸, y軸は 2、z軸は n（ここでは例として n = 5）
-const X_DIM: u32 = 2;
-const Y_DIM: u32 = 3;
-const Z_DIM: u32 = 19; // ここを変えることで n の値を調整可能
-const TOT: u32 = X_DIM * Y_DIM * Z_DIM; // 全ブロック数（2*2*n = 4*n）
-
-/// インデックス -> 座標 (x, y, z) への変換
-fn index_to_coord(i: u32) -> (u32, u32, u32) {
-    let x = i % X_DIM;
-    let y = (i / X_DIM) % Y_DIM;
-    let z = i / (X_DIM * Y_DIM);
-    (x, y, z)
-}
-
-/// 座標 a が座標 b 以上か（各成分について a.0>=b.0, a.1>=b.1, a.2>=b.2）
-fn coord_ge(a: (u32, u32, u32), b: (u32, u32, u32)) -> bool {
-    a.0 >= b.0 && a.1 >= b.1 && a.2 >= b.2
-}
-
-/// 選んだ座標 chosen 以上の座標を持つブロック群を取り除くためのマスクを返す
-fn removal_mask(chosen: (u32, u32, u32)) -> u128 {
-    let mut mask: u128 = 0;
-    for i in 0..TOT {
-        let coord = index_to_coord(i);
-        if coord_ge(coord, chosen) {
-            mask |= 1 << i;
-        }
-    }
-    mask
-}
-
-/// 現在の状態 state（各ブロックの存在をビットで表現）から、合法な手を返す。
-/// 手は (chosen: (x,y,z), new_state: u128) の組として返す。
-/// 毒ブロック (0,0,0) は選べない手としています。
-fn legal_moves(state: u128) -> Vec<((u32, u32, u32), u128)> {
-    let mut moves = Vec::new();
-    for i in 0..TOT {
-        // ブロック i が存在しているかチェック
-        if state & (1 << i) != 0 {
-            let chosen = index_to_coord(i);
-            if chosen == (0, 0, 0) {
-                continue; // 毒ブロックは選べない
+
+use chomp::db::{load, save, Checkpoint};
+use chomp::grundy::{distribution, grundy, misere_grundy};
+use chomp::progress::{format_bytes, Progress};
+use chomp::ptable::PTable;
+use chomp::render::render;
+use chomp::sum::GameSum;
+use chomp::summary::Summary;
+use chomp::svg::isometric_svg;
+use chomp::sweep::{Sweep, SweepResult};
+use chomp::{
+    retrograde, retrograde_into, win_with, winning_moves_with, Bits, Board, BoundedMemo, DenseMemo, Error, Memo, Ranker, Ruleset,
+    State,
+};
+use cli::{parse_args, Command, MemoKind, Options, SolverKind};
+use output::{board_columns, board_json, csv_row, summary_json, Format, Json, BOARD_COLUMNS};
+use play::play;
+
+/// --memo bounded の表の局面数がこの倍率をかけても盤面の局面数に届かなければ警告する
+const BOUNDED_SLACK: u64 = 8;
+
+/// 盤面を状態型 S で解き、結果を表示する
+fn run<S: State>(options: &Options) -> Result<(), Error> {
+    for board in &options.boards {
+        board.check_capacity::<S>()?;
+    }
+    let board = &options.boards[0];
+    let initial_state: S = match &options.position {
+        Some(text) => board.parse_position(text)?,
+        None => board.full_state(),
+    };
+    match options.command {
+        Command::Solve => {}
+        Command::Grundy => return solve_grundy::<S>(board, initial_state),
+        Command::Sum => return solve_sum::<S>(&options.boards),
+        Command::Play => {
+            let view = options.render.map(|style| (style, options.charset));
+            return play::<S>(board, initial_state, options.engine_first, view, options.parallelism);
+        }
+        Command::Moves => return list_moves::<S>(board, initial_state, options),
+        Command::Sweep => return sweep::<S>(options),
+        Command::Export => return export::<S>(board, initial_state, options),
+        Command::Verify => return verify::<S>(board, initial_state, options),
+    }
+    match (options.solver, options.memo) {
+        (SolverKind::Recursive, MemoKind::Hash) => solve_recursive::<S, _>(board, initial_state, options, &DashMap::new()),
+        (SolverKind::Recursive, MemoKind::Bounded) => {
+            let memo = BoundedMemo::new(options.memo_size);
+            note(options, format!("置換表: {} 局面（{} バイト）", memo.capacity(), memo.bytes()));
+            // 表が局面数よりずっと小さいと、追い出した局面を何度も解き直して探索の時間が指数的に増える
+            if let Ok(count) = Ranker::count_positions(board) {
+                if memo.capacity().saturating_mul(BOUNDED_SLACK) < count {
+                    eprintln!(
+                        "警告: 置換表の {} 局面は盤面の局面数 {} よりずっと少ないため、解き直しが増えて非常に時間がかかることがあります（--memo-size を大きくしてください）",
+                        memo.capacity(),
+                        count
+                    );
+                }
             }
-            let rm_mask = removal_mask(chosen);
-            // もし取り除くブロック群に毒ブロック (0,0,0)（インデックス 0）が含まれていたら不合法
-            if rm_mask & 1 != 0 {
-                continue;
+            solve_recursive::<S, _>(board, initial_state, options, &memo)
+        }
+        (SolverKind::Recursive, MemoKind::Dense) => {
+            let memo = DenseMemo::new(Ranker::new(board)?);
+            note_dense_size::<S>(options, &memo);
+            solve_recursive::<S, _>(board, initial_state, options, &memo)
+        }
+        (SolverKind::Retrograde, _) => {
+            let memo = DenseMemo::new(Ranker::new(board)?);
+            note_dense_size::<S>(options, &memo);
+            note(options, "後退解析を開始...".to_string());
+            // 集計の計算時間には後退解析の時間も含める
+            let start = Instant::now();
+            with_progress::<S, _, _>(board, options, &memo, |progress| retrograde_into::<S>(board, &memo, progress));
+            note(options, format!("P 局面数: {}（後退解析 {:.2} 秒）", memo.losing_count(), start.elapsed().as_secs_f64()));
+            solve::<S, _>(board, initial_state, options, &memo, start)?;
+            if let Some(path) = &options.db {
+                let count = save::<S, _>(path, board, &memo)?;
+                note(options, format!("データベース {} に {} 局面を書き出しました", path, count));
             }
-            let new_state = state & !rm_mask;
-            moves.push((chosen, new_state));
+            Ok(())
         }
     }
-    moves
 }
 
-/// 現在の状態 state で、手番のプレイヤーが勝てるかどうかを並列再帰的に判定する関数。
-/// memo は Arc 化した DashMap を用いて並列安全にメモ化します。
-fn win(state: u128, memo: &Arc<DashMap<u128, bool>>) -> bool {
-    // 終端状態：毒ブロックのみが残っている場合は負け
-    if state == 1 {
-        return false;
+/// 再帰的な探索で解く。データベースを指定していれば、既存の解を読み込んでから解き始め、
+/// 途中経過を一定時間ごとに、結果を最後に書き出す
+fn solve_recursive<S: State, M: Memo<S>>(board: &Board, initial_state: S, options: &Options, memo: &M) -> Result<(), Error> {
+    let Some(path) = &options.db else {
+        return solve(board, initial_state, options, memo, Instant::now());
+    };
+    if Path::new(path).exists() {
+        let count = load(path, board, memo)?;
+        note(options, format!("データベース {} から {} 局面を読み込みました", path, count));
     }
-    if let Some(res) = memo.get(&state) {
-        return *res;
+    let checkpoint = Checkpoint::new(memo, board, path, Duration::from_secs(options.checkpoint)).with_report(|result| match result {
+        Ok(count) => eprintln!("チェックポイント: {} 局面を {} に書き出しました", count, path),
+        Err(e) => eprintln!("チェックポイントを書き出せません: {}", e),
+    });
+    solve(board, initial_state, options, &checkpoint, Instant::now())?;
+    if checkpoint.saved() > 0 {
+        note(options, format!("途中経過を {} 回書き出しました", checkpoint.saved()));
     }
-    let moves = legal_moves(state);
-    if moves.is_empty() {
-        memo.insert(state, false);
-        return false;
+    let count = save(path, board, memo)?;
+    note(options, format!("データベース {} に {} 局面を書き出しました", path, count));
+    Ok(())
+}
+
+/// 局面 initial_state の勝敗と必勝手を求め、--format の形式で表示する。
+/// 集計の計算時間は start から数える（後退解析で表を作ってから呼ぶなら、その前の時刻を渡す）
+fn solve<S: State, M: Memo<S>>(
+    board: &Board,
+    initial_state: S,
+    options: &Options,
+    memo: &M,
+    start: Instant,
+) -> Result<(), Error> {
+    if options.format == Format::Text {
+        println!("盤面 {}（規約 {}）の計算開始...", board, board.ruleset());
+        print_cells(board, "毒ブロック", board.poison());
+        print_cells(board, "選べないブロック", board.forbidden());
+        print_position(board, initial_state)?;
+        if let Some(style) = options.render {
+            println!("凡例: {}", options.charset);
+            print!("{}", render(board, initial_state, S::zero(), style, options.charset)?);
+        }
     }
-    // 合法手について、Rayon の par_iter() を使って並列に再帰的に評価
-    let winning = moves.par_iter().any(|&(_, new_state)| {
-        !win(new_state, memo)
+    // 必勝手を調べるときに解く局面も進み具合に含める
+    let (first_win, moves) = with_progress(board, options, memo, |progress| {
+        let first_win = win_with(board, initial_state, memo, options.parallelism, progress);
+        (first_win, winning_moves_with(board, initial_state, memo, options.parallelism, progress))
     });
-    memo.insert(state, winning);
-    winning
+    let summary = Summary::new(memo, start.elapsed());
+    let poisoned = board.poisoned_moves(initial_state);
+
+    match options.format {
+        Format::Text => {
+            println!("初期状態は先手必勝か: {}", first_win);
+            println!("先手の必勝手候補:");
+            for &mv in &moves {
+                if board.dims().is_some() {
+                    // 箱の盤面なら手を指した後の局面も高さ行列で示す
+                    let after = initial_state & !board.removal_mask::<S>(mv);
+                    println!("{} -> {}", board.cell_name(mv), board.state_to_heights(after)?);
+                    if let Some(style) = options.render {
+                        print!("{}", render(board, initial_state, board.removal_mask::<S>(mv), style, options.charset)?);
+                    }
+                } else {
+                    println!("{}", board.cell_name(mv));
+                }
+            }
+            print_cells(board, "選ぶとその場で負ける手", &poisoned);
+            println!("{}", summary);
+        }
+        Format::Json => {
+            let moves = moves
+                .iter()
+                .map(|&mv| Ok(Json::Obj(move_fields(board, mv, initial_state & !board.removal_mask::<S>(mv))?)))
+                .collect::<Result<_, Error>>()?;
+            let mut fields = output::header("solve");
+            fields.extend([
+                ("board", board_json(board)),
+                ("position", Json::str(board.format_position(initial_state)?)),
+                ("first_player_wins", Json::Bool(first_win)),
+                ("winning_moves", Json::Arr(moves)),
+                ("poisoned_moves", Json::strs(poisoned.iter().map(|&i| board.cell_name(i)))),
+                ("stats", summary_json(&summary)),
+            ]);
+            println!("{}", Json::Obj(fields));
+        }
+        Format::Csv => {
+            let columns = ["position", "first_player_wins", "winning_moves", "table_positions", "p_positions",
+                "n_positions", "table_bytes", "seconds"];
+            println!("{}", csv_row(&[&BOARD_COLUMNS[..], &columns].concat()));
+            let mut row = board_columns(board);
+            row.extend([
+                board.format_position(initial_state)?,
+                first_win.to_string(),
+                cell_names(board, &moves),
+                summary.positions().to_string(),
+                summary.p.to_string(),
+                summary.n.to_string(),
+                summary.bytes.to_string(),
+                format!("{:.3}", summary.elapsed.as_secs_f64()),
+            ]);
+            println!("{}", csv_row(&row));
+        }
+    }
+    if let Some(path) = &options.svg {
+        fs::write(path, isometric_svg(board, initial_state, &moves)?)
+            .map_err(|e| Error::Io(format!("{} に書き込めません: {}", path, e)))?;
+        note(options, format!("SVG を {} に書き出しました", path));
+    }
+    Ok(())
+}
+
+/// --progress を指定していれば、f を実行する間 memo の表の進み具合を表示する。f には記録先の Progress を渡す
+fn with_progress<S: State, M: Memo<S>, T>(
+    board: &Board,
+    options: &Options,
+    memo: &M,
+    f: impl FnOnce(Option<&Progress>) -> T,
+) -> T {
+    let Some(interval) = options.progress else {
+        return f(None);
+    };
+    let progress = Progress::new(Ranker::count_positions(board).ok());
+    thread::scope(|scope| {
+        scope.spawn(|| progress.report_every(interval, memo));
+        let res = f(Some(&progress));
+        progress.finish();
+        res
+    })
 }
 
-/// 現在の状態から、勝利につながる（必勝となる）手（chosen 座標）の候補をすべて返す
-fn winning_moves(state: u128, memo: &Arc<DashMap<u128, bool>>) -> Vec<(u32, u32, u32)> {
-    legal_moves(state)
+/// 局面のすべての合法手と、指した後の局面で相手が勝つかどうかを --format の形式で表示する
+fn list_moves<S: State>(board: &Board, initial_state: S, options: &Options) -> Result<(), Error> {
+    let memo = DashMap::new();
+    let moves: Vec<(u32, S, bool)> = board
+        .legal_moves(initial_state)
         .into_iter()
-        .filter_map(|(mv, new_state)| if !win(new_state, memo) { Some(mv) } else { None })
-        .collect()
+        .map(|(mv, after)| (mv, after, !win_with(board, after, &memo, options.parallelism, None)))
+        .collect();
+    let poisoned = board.poisoned_moves(initial_state);
+    match options.format {
+        Format::Text => {
+            println!("盤面 {}（規約 {}）の合法手:", board, board.ruleset());
+            print_position(board, initial_state)?;
+            for &(mv, after, wins) in &moves {
+                let result = if wins { "必勝手" } else { "相手の勝ち" };
+                if board.dims().is_some() {
+                    println!("{} -> {}  {}", board.cell_name(mv), board.state_to_heights(after)?, result);
+                } else {
+                    println!("{}  {}", board.cell_name(mv), result);
+                }
+            }
+            print_cells(board, "選ぶとその場で負ける手", &poisoned);
+        }
+        Format::Json => {
+            // 毒を食べる手も result を "poisoned"、指した後の局面を null として並べる
+            let mut entries = Vec::new();
+            for &(mv, after, wins) in &moves {
+                let mut fields = move_fields(board, mv, after)?;
+                fields.push(("result", Json::str(if wins { "win" } else { "lose" })));
+                entries.push(Json::Obj(fields));
+            }
+            entries.extend(poisoned.iter().map(|&mv| {
+                Json::Obj(vec![
+                    ("cell", Json::str(board.cell_name(mv))),
+                    ("position", Json::Null),
+                    ("result", Json::str("poisoned")),
+                ])
+            }));
+            let mut fields = output::header("moves");
+            fields.extend([
+                ("board", board_json(board)),
+                ("position", Json::str(board.format_position(initial_state)?)),
+                ("moves", Json::Arr(entries)),
+            ]);
+            println!("{}", Json::Obj(fields));
+        }
+        Format::Csv => {
+            println!("{}", csv_row(&[&BOARD_COLUMNS[..], &["position", "move", "after", "result"]].concat()));
+            let position = board.format_position(initial_state)?;
+            let print_row = |mv: u32, after: String, result: &str| {
+                let mut row = board_columns(board);
+                row.extend([position.clone(), board.cell_name(mv).to_string(), after, result.to_string()]);
+                println!("{}", csv_row(&row));
+            };
+            for &(mv, after, wins) in &moves {
+                print_row(mv, board.format_position(after)?, if wins { "win" } else { "lose" });
+            }
+            for &mv in &poisoned {
+                print_row(mv, String::new(), "poisoned");
+            }
+        }
+    }
+    Ok(())
 }
 
-fn main() {
-    // 初期状態: 全ブロックが存在している → 下位 TOT ビットがすべて 1
-    let initial_state: u128 = (1u128 << TOT) - 1;
-    let memo = Arc::new(DashMap::new());
-    
-    println!("計算開始...");
-    let first_win = win(initial_state, &memo);
-    println!("初期状態は先手必勝か: {}", first_win);
-    
-    let moves = winning_moves(initial_state, &memo);
+/// 手 mv のブロックの名前と、指した後の局面 after の JSON の項目
+fn move_fields<S: State>(board: &Board, mv: u32, after: S) -> Result<Vec<(&'static str, Json)>, Error> {
+    Ok(vec![("cell", Json::str(board.cell_name(mv))), ("position", Json::str(board.format_position(after)?))])
+}
+
+/// 大きさの違う盤面を Sweep で順に解き、1 つの盤面を 1 行（盤面、先手必勝か、必勝手、P 局面数
+/// （再帰的に探索した盤面では "-"）、前の盤面から引き継いだ局面数、計算時間）で表示する。
+/// --format json では全盤面の結果を最後に 1 つの文書として表示する
+fn sweep<S: State>(options: &Options) -> Result<(), Error> {
+    match options.format {
+        Format::Text => println!("盤面\t先手必勝\t必勝手\tP 局面数\t引き継いだ局面\t時間（秒）"),
+        Format::Json => {}
+        Format::Csv => {
+            let columns = ["first_player_wins", "winning_moves", "p_positions", "reused_positions", "seconds"];
+            println!("{}", csv_row(&[&BOARD_COLUMNS[..], &columns].concat()));
+        }
+    }
+    let mut results = Vec::new();
+    let mut sweep = Sweep::<S>::new(options.parallelism);
+    for board in &options.boards {
+        let state: S = board.full_state();
+        let SweepResult { first_win, moves, losing, reused, elapsed } = sweep.solve(board);
+        let seconds = elapsed.as_secs_f64();
+        match options.format {
+            Format::Text => println!(
+                "{}\t{}\t{}\t{}\t{}\t{:.3}",
+                board,
+                first_win,
+                moves.iter().map(|&mv| board.cell_name(mv)).collect::<Vec<_>>().join(" "),
+                losing.map_or("-".to_string(), |n| n.to_string()),
+                reused,
+                seconds
+            ),
+            Format::Json => results.push(Json::Obj(vec![
+                ("board", board_json(board)),
+                ("position", Json::str(board.format_position(state)?)),
+                ("first_player_wins", Json::Bool(first_win)),
+                ("winning_moves", Json::strs(moves.iter().map(|&mv| board.cell_name(mv)))),
+                ("p_positions", losing.map_or(Json::Null, Json::Int)),
+                ("reused_positions", Json::Int(reused)),
+                ("seconds", Json::Num(seconds)),
+            ])),
+            Format::Csv => {
+                let mut row = board_columns(board);
+                row.extend([
+                    first_win.to_string(),
+                    cell_names(board, &moves),
+                    losing.map_or(String::new(), |n| n.to_string()),
+                    reused.to_string(),
+                    format!("{:.3}", seconds),
+                ]);
+                println!("{}", csv_row(&row));
+            }
+        }
+    }
+    if options.format == Format::Json {
+        let mut fields = output::header("sweep");
+        fields.push(("results", Json::Arr(results)));
+        println!("{}", Json::Obj(fields));
+    }
+    Ok(())
+}
+
+/// 局面を解いた結果を --output のファイルに書き出す。拡張子が .svg なら局面と必勝手の図を、
+/// .db なら解のデータベースを、.ptable と .txt なら盤面のすべての P 局面の表を
+/// （後退解析で解き、それぞれバイナリ形式とテキスト形式で）書き出す
+fn export<S: State>(board: &Board, initial_state: S, options: &Options) -> Result<(), Error> {
+    let path = options.output.as_deref().unwrap();
+    let extension = Path::new(path).extension().and_then(|e| e.to_str());
+    match extension {
+        Some("svg") | Some("db") => {
+            let memo = DashMap::new();
+            win_with(board, initial_state, &memo, options.parallelism, None);
+            if extension == Some("svg") {
+                let moves = winning_moves_with(board, initial_state, &memo, options.parallelism, None);
+                fs::write(path, isometric_svg(board, initial_state, &moves)?)
+                    .map_err(|e| Error::Io(format!("{} に書き込めません: {}", path, e)))?;
+                println!("局面と必勝手の図を {} に書き出しました", path);
+            } else {
+                let count = save(path, board, &memo)?;
+                println!("データベース {} に {} 局面を書き出しました", path, count);
+            }
+        }
+        Some("ptable") | Some("txt") => {
+            let memo = retrograde::<S>(board, Ranker::new(board)?);
+            let table = PTable::<S>::from_memo(board, memo.ranker(), &memo)?;
+            if extension == Some("ptable") {
+                table.write_binary(path, board)?;
+            } else {
+                table.write_text(path, board)?;
+            }
+            println!("P 局面の表 {} に {} 局面を書き出しました（全局面 {}）", path, table.positions().len(), Memo::<S>::entries(&memo));
+            println!("残りブロック数ごとの P 局面数:");
+            for (cells, &count) in table.counts().iter().enumerate().filter(|&(_, &count)| count > 0) {
+                println!("{}: {}", cells, count);
+            }
+        }
+        _ => {
+            return Err(Error::InvalidOutput(format!(
+                "{} の形式が分かりません（拡張子は .svg, .db, .ptable, .txt のいずれか）",
+                path
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// 再帰的な探索と後退解析、--db を指定していればそのデータベースの結果が一致するか確かめ、
+/// 照合ごとの結果を表示する。一致しなければエラー
+fn verify<S: State>(board: &Board, initial_state: S, options: &Options) -> Result<(), Error> {
+    println!("盤面 {}（規約 {}）を検証します", board, board.ruleset());
+    let result = chomp::verify::verify(board, initial_state, options.parallelism, options.db.as_deref())?;
+    println!("再帰的な探索（hash）: 先手必勝か {}、必勝手 {} 個", result.first_win, result.moves.len());
+    if let Some(e) = &result.skipped {
+        println!("後退解析との照合は省きます: {}", e);
+    }
+    for check in &result.checks {
+        if check.mismatched == 0 {
+            println!("{}: 一致（{} 局面）", check.name, check.total);
+        } else {
+            println!("{}: {} 局面中 {} 局面が不一致", check.name, check.total, check.mismatched);
+        }
+    }
+    let failures = result.failures();
+    if failures.is_empty() {
+        println!("検証: すべて一致");
+        Ok(())
+    } else {
+        Err(Error::VerificationFailed(failures.join("、")))
+    }
+}
+
+/// 順位で引く表の局面数と大きさ（順位付けの表を含む）を知らせる
+fn note_dense_size<S: State>(options: &Options, memo: &DenseMemo) {
+    let bytes = format_bytes(Memo::<S>::approx_bytes(memo));
+    note(options, format!("局面数: {}（表の大きさ 約 {}）", memo.ranker().count(), bytes));
+}
+
+/// 結果以外の途中の知らせを表示する。--format が text 以外なら結果と混ざらないよう標準エラー出力に出す
+fn note(options: &Options, message: String) {
+    if options.format == Format::Text {
+        println!("{}", message);
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+/// ブロックの名前を ";" で区切って並べる
+fn cell_names(board: &Board, cells: &[u32]) -> String {
+    cells.iter().map(|&i| board.cell_name(i)).collect::<Vec<_>>().join(";")
+}
+
+/// 初期状態を、箱の盤面なら高さ行列で、それ以外なら全ブロックでない場合に残っているブロックの並びで表示する
+fn print_position<S: State>(board: &Board, state: S) -> Result<(), Error> {
+    if board.dims().is_some() {
+        println!("初期状態（高さ行列）: {}", board.state_to_heights(state)?);
+    } else if state != board.full_state() {
+        let cells: Vec<u32> = (0..board.tot()).filter(|&i| state.has(i)).collect();
+        print_cells(board, "初期状態で残っているブロック", &cells);
+    }
+    Ok(())
+}
+
+/// ブロックの並びを 1 行で表示する。空なら何も表示しない
+fn print_cells(board: &Board, label: &str, cells: &[u32]) {
+    if !cells.is_empty() {
+        let cells: Vec<&str> = cells.iter().map(|&i| board.cell_name(i)).collect();
+        println!("{}: {}", label, cells.join(" "));
+    }
+}
+
+/// 初期局面と、各手を指した後の局面の Grundy 値、および到達可能な局面の Grundy 値の分布を表示する。
+/// misère の規約では misère Grundy 値を求める
+fn solve_grundy<S: State>(board: &Board, initial_state: S) -> Result<(), Error> {
+    let memo = DashMap::new();
+    let misere = board.ruleset() == Ruleset::Misere;
+    let name = if misere { "misère Grundy 値" } else { "Grundy 値" };
+    let value_of = |state: S| {
+        if misere { misere_grundy(board, state, &memo) } else { grundy(board, state, &memo) }
+    };
+
+    println!("盤面 {}（規約 {}）の {} の計算開始...", board, board.ruleset(), name);
+    print_position(board, initial_state)?;
+    println!("初期状態の {}: {}", name, value_of(initial_state));
+    println!("各手の後の {}:", name);
+    for (mv, new_state) in board.legal_moves(initial_state) {
+        println!("{} -> {}", board.cell_name(mv), value_of(new_state));
+    }
+    println!("到達可能な局面の {} の分布:", name);
+    for (value, count) in distribution(&memo).iter().enumerate() {
+        println!("{}: {}", value, count);
+    }
+    Ok(())
+}
+
+/// 盤面の直和を Grundy 値で解き、直接探索の結果と照らし合わせて表示する
+fn solve_sum<S: State>(boards: &[Board]) -> Result<(), Error> {
+    let game = GameSum::new(boards.iter().map(|b| (b.clone(), b.full_state::<S>())).collect());
+    let names: Vec<String> = boards.iter().map(Board::to_string).collect();
+
+    println!("盤面の和 {} の計算開始...", names.join(" + "));
+    for (i, board) in boards.iter().enumerate() {
+        println!("盤面 {} ({}) の Grundy 値: {}", i, board, game.component_grundy(i));
+    }
+    println!("和の Grundy 値: {}", game.grundy());
+    println!("初期状態は先手必勝か: {}", game.win());
+    let moves = game.winning_moves();
     println!("先手の必勝手候補:");
-    for mv in moves {
-        println!("{:?}", mv);
+    for mv in &moves {
+        println!("盤面 {} の {}", mv.component, boards[mv.component].cell_name(mv.cell));
+    }
+
+    // 直接探索で確認する
+    let by_search = game.win_by_search();
+    let mut moves_by_search = game.winning_moves_by_search();
+    moves_by_search.sort_by_key(|mv| mv.component);
+    if by_search == game.win() && moves_by_search == moves {
+        println!("直接探索による確認: 一致");
+    } else {
+        println!("直接探索による確認: 不一致（直接探索では先手必勝か: {}、必勝手 {} 個）", by_search, moves_by_search.len());
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().expect("スレッドプールを作れません");
+    }
+
+    // ブロック数に応じて、収まる最小の状態型を選ぶ
+    let tot = options.boards.iter().map(Board::tot).max().unwrap();
+    let result = match tot {
+        0..=128 => run::<u128>(&options),
+        129..=256 => run::<Bits<4>>(&options),
+        257..=512 => run::<Bits<8>>(&options),
+        _ => run::<Bits<16>>(&options),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
     }
 }
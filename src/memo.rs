@@ -0,0 +1,277 @@
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use dashmap::DashMap;
+
+use crate::rank::Ranker;
+use crate::state::State;
+
+/// 解いた局面の勝敗を覚えておく表。並列探索から共有されるので Sync であること
+pub trait Memo<S>: Sync {
+    fn get(&self, state: &S) -> Option<bool>;
+    fn insert(&self, state: S, win: bool);
+    /// 表にあるすべての局面と勝敗を f に渡す（順序は不定）
+    fn for_each_entry(&self, f: &mut dyn FnMut(S, bool));
+    /// 表にある局面の数
+    fn entries(&self) -> u64;
+    /// 表が使っているおおよそのバイト数
+    fn approx_bytes(&self) -> u64;
+}
+
+impl<S: State> Memo<S> for DashMap<S, bool> {
+    fn get(&self, state: &S) -> Option<bool> {
+        DashMap::get(self, state).map(|r| *r)
+    }
+
+    fn insert(&self, state: S, win: bool) {
+        DashMap::insert(self, state, win);
+    }
+
+    fn for_each_entry(&self, f: &mut dyn FnMut(S, bool)) {
+        for entry in self.iter() {
+            f(*entry.key(), *entry.value());
+        }
+    }
+
+    fn entries(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn approx_bytes(&self) -> u64 {
+        // 各枠に局面と勝敗、それに制御用の 1 バイト
+        (self.capacity() * (mem::size_of::<(S, bool)>() + 1)) as u64
+    }
+}
+
+/// 局面の順位で引く平坦なビット列の表。1 局面あたり 2 ビット（既知か、勝ちか）しか使わない
+pub struct DenseMemo {
+    ranker: Ranker,
+    /// 1 語に 32 局面分。局面 r は語 r / 32 の第 2*(r % 32) ビット（既知）と次のビット（勝ち）
+    words: Vec<AtomicU64>,
+}
+
+impl DenseMemo {
+    pub fn new(ranker: Ranker) -> DenseMemo {
+        let len = ranker.count().div_ceil(32) as usize;
+        DenseMemo { ranker, words: (0..len).map(|_| AtomicU64::new(0)).collect() }
+    }
+
+    /// 局面を順位付けする表
+    pub fn ranker(&self) -> &Ranker {
+        &self.ranker
+    }
+
+    /// 順位 r の局面の勝敗が表にあるか
+    pub(crate) fn known(&self, r: u64) -> bool {
+        self.words[(r / 32) as usize].load(Ordering::Relaxed) >> (2 * (r % 32)) & 1 != 0
+    }
+
+    /// 表に負け（P 局面）として記録されている局面の数
+    pub fn losing_count(&self) -> u64 {
+        // 各 2 ビットの組のうち、既知ビットが立ち勝ちビットが立っていないもの
+        const KNOWN: u64 = 0x5555_5555_5555_5555;
+        self.words
+            .iter()
+            .map(|w| {
+                let w = w.load(Ordering::Relaxed);
+                (w & KNOWN & !(w >> 1)).count_ones() as u64
+            })
+            .sum()
+    }
+}
+
+impl<S: State> Memo<S> for DenseMemo {
+    fn get(&self, state: &S) -> Option<bool> {
+        let r = self.ranker.rank(*state)?;
+        let bits = self.words[(r / 32) as usize].load(Ordering::Relaxed) >> (2 * (r % 32));
+        if bits & 1 != 0 { Some(bits & 2 != 0) } else { None }
+    }
+
+    fn insert(&self, state: S, win: bool) {
+        if let Some(r) = self.ranker.rank(state) {
+            let bits = (1 | (win as u64) << 1) << (2 * (r % 32));
+            self.words[(r / 32) as usize].fetch_or(bits, Ordering::Relaxed);
+        }
+    }
+
+    fn for_each_entry(&self, f: &mut dyn FnMut(S, bool)) {
+        for (w, word) in self.words.iter().enumerate() {
+            let word = word.load(Ordering::Relaxed);
+            for k in 0..32 {
+                let bits = word >> (2 * k);
+                if bits & 1 != 0 {
+                    f(self.ranker.unrank(w as u64 * 32 + k), bits & 2 != 0);
+                }
+            }
+        }
+    }
+
+    fn entries(&self) -> u64 {
+        const KNOWN: u64 = 0x5555_5555_5555_5555;
+        self.words.iter().map(|w| (w.load(Ordering::Relaxed) & KNOWN).count_ones() as u64).sum()
+    }
+
+    fn approx_bytes(&self) -> u64 {
+        // 順位付けの表も、この表を引くのに欠かせないので含める
+        (self.words.len() * mem::size_of::<AtomicU64>()) as u64 + self.ranker.approx_bytes()
+    }
+}
+
+/// 大きさを固定した置換表の 1 項目
+#[derive(Clone, Copy)]
+struct Entry<S> {
+    state: S,
+    win: bool,
+    /// 局面のブロック数。多いほど解き直すのに手間がかかるので残す価値が高い
+    size: u32,
+}
+
+/// 置換表のバケット。2 段になっていて、deep にはバケットに来た中でブロック数の多い局面を、
+/// recent には deep に入れなかった（または deep から追い出された）直近の局面を置く
+#[derive(Clone, Copy)]
+struct Bucket<S> {
+    deep: Option<Entry<S>>,
+    recent: Option<Entry<S>>,
+}
+
+/// 使うメモリの上限を決めた置換表。表が一杯になると、バケットごとに
+/// ブロック数の多い局面を優先して残し、残りは新しい局面で上書きする。
+/// 上書きで失われた局面は解き直すだけなので、勝敗の結果には影響しない。
+/// バケットごとに小さなロックを持ち、別のバケットへの読み書きは互いに待たない
+pub struct BoundedMemo<S> {
+    buckets: Vec<Mutex<Bucket<S>>>,
+    hasher: BuildHasherDefault<DefaultHasher>,
+}
+
+impl<S: State> BoundedMemo<S> {
+    /// 高々 bytes バイトを使う表を作る（最低 1 バケット）
+    pub fn new(bytes: u64) -> BoundedMemo<S> {
+        let per_bucket = mem::size_of::<Mutex<Bucket<S>>>() as u64;
+        let count = (bytes / per_bucket).max(1);
+        let empty = Bucket { deep: None, recent: None };
+        BoundedMemo {
+            buckets: (0..count).map(|_| Mutex::new(empty)).collect(),
+            hasher: BuildHasherDefault::default(),
+        }
+    }
+
+    /// 覚えておける局面の数
+    pub fn capacity(&self) -> u64 {
+        2 * self.buckets.len() as u64
+    }
+
+    /// 表が使うメモリのバイト数
+    pub fn bytes(&self) -> u64 {
+        (self.buckets.len() * mem::size_of::<Mutex<Bucket<S>>>()) as u64
+    }
+
+    fn bucket(&self, state: &S) -> &Mutex<Bucket<S>> {
+        let h = self.hasher.hash_one(state);
+        &self.buckets[(h % self.buckets.len() as u64) as usize]
+    }
+}
+
+impl<S: State> Memo<S> for BoundedMemo<S> {
+    fn get(&self, state: &S) -> Option<bool> {
+        let bucket = self.bucket(state).lock().unwrap();
+        [bucket.deep, bucket.recent].into_iter().flatten().find(|e| e.state == *state).map(|e| e.win)
+    }
+
+    fn insert(&self, state: S, win: bool) {
+        let entry = Entry { state, win, size: state.count_ones() };
+        let mut bucket = self.bucket(&state).lock().unwrap();
+        match (bucket.deep, bucket.recent) {
+            (Some(e), _) if e.state == state => bucket.deep = Some(entry),
+            (_, Some(e)) if e.state == state => bucket.recent = Some(entry),
+            // deep より大きい局面なら deep に入れ、追い出した局面は recent に回す
+            (None, _) => bucket.deep = Some(entry),
+            (Some(e), _) if entry.size >= e.size => {
+                bucket.recent = bucket.deep;
+                bucket.deep = Some(entry);
+            }
+            _ => bucket.recent = Some(entry),
+        }
+    }
+
+    fn for_each_entry(&self, f: &mut dyn FnMut(S, bool)) {
+        for bucket in &self.buckets {
+            let bucket = *bucket.lock().unwrap();
+            for e in [bucket.deep, bucket.recent].into_iter().flatten() {
+                f(e.state, e.win);
+            }
+        }
+    }
+
+    fn entries(&self) -> u64 {
+        self.buckets
+            .iter()
+            .map(|b| {
+                let b = b.lock().unwrap();
+                b.deep.is_some() as u64 + b.recent.is_some() as u64
+            })
+            .sum()
+    }
+
+    fn approx_bytes(&self) -> u64 {
+        self.bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// バケットが 1 つだけの表。どの局面も同じバケットに入る
+    fn single_bucket() -> BoundedMemo<u128> {
+        let memo = BoundedMemo::new(0);
+        assert_eq!(memo.capacity(), 2);
+        memo
+    }
+
+    #[test]
+    fn larger_entry_moves_deep_to_recent() {
+        let memo = single_bucket();
+        memo.insert(0b1, true);
+        memo.insert(0b111, false);
+        // 大きい局面が deep に入り、前の deep は recent に残る
+        assert_eq!(memo.get(&0b1), Some(true));
+        assert_eq!(memo.get(&0b111), Some(false));
+        memo.insert(0b11111, true);
+        assert_eq!(memo.get(&0b11111), Some(true));
+        assert_eq!(memo.get(&0b111), Some(false));
+        assert_eq!(memo.get(&0b1), None);
+    }
+
+    #[test]
+    fn smaller_entry_only_overwrites_recent() {
+        let memo = single_bucket();
+        memo.insert(0b11111, true);
+        memo.insert(0b1, false);
+        memo.insert(0b11, true);
+        // deep の大きい局面は残り、recent だけが入れ替わる
+        assert_eq!(memo.get(&0b11111), Some(true));
+        assert_eq!(memo.get(&0b11), Some(true));
+        assert_eq!(memo.get(&0b1), None);
+        assert_eq!(memo.entries(), 2);
+    }
+
+    #[test]
+    fn evicted_entries_are_not_returned() {
+        let memo = single_bucket();
+        memo.insert(0b111, false);
+        memo.insert(0b1, true);
+        memo.insert(0b11, false);
+        memo.insert(0b101, false);
+        // 追い出された局面は古い勝敗を返さず、未知になる
+        assert_eq!(memo.get(&0b1), None);
+        assert_eq!(memo.get(&0b11), None);
+        assert_eq!(memo.get(&0b111), Some(false));
+        assert_eq!(memo.get(&0b101), Some(false));
+        let mut seen = Vec::new();
+        memo.for_each_entry(&mut |state, _| seen.push(state));
+        seen.sort();
+        assert_eq!(seen, [0b101, 0b111]);
+    }
+}
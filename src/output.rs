@@ -0,0 +1,209 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chomp::summary::Summary;
+use chomp::{Board, Error, VERSION};
+
+/// 機械向けの出力の形式の版。項目の意味を変えたり消したりしたら上げる（項目を足すだけなら上げない）
+pub const SCHEMA: u32 = 1;
+
+/// 結果の出力形式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// 人が読むための表示
+    #[default]
+    Text,
+    /// JSON の文書 1 つ
+    Json,
+    /// 見出しの行つきの CSV
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(Error::InvalidOutput(format!("出力形式は text, json, csv のいずれかです: {}", s))),
+        }
+    }
+}
+
+/// JSON の値。Display で空白のない 1 行の JSON として書き出す
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(u64),
+    Num(f64),
+    Str(String),
+    Arr(Vec<Json>),
+    /// 項目は並べた順に書き出す
+    Obj(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    pub fn str(s: impl Into<String>) -> Json {
+        Json::Str(s.into())
+    }
+
+    /// 文字列の並びの配列
+    pub fn strs<T: AsRef<str>>(items: impl IntoIterator<Item = T>) -> Json {
+        Json::Arr(items.into_iter().map(|s| Json::str(s.as_ref())).collect())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(n) => write!(f, "{}", n),
+            Json::Num(x) if x.is_finite() => write!(f, "{}", x),
+            Json::Num(_) => write!(f, "null"),
+            Json::Str(s) => write_json_string(f, s),
+            Json::Arr(items) => {
+                write!(f, "[")?;
+                for (k, item) in items.iter().enumerate() {
+                    if k > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Obj(fields) => {
+                write!(f, "{{")?;
+                for (k, (key, value)) in fields.iter().enumerate() {
+                    if k > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// 文字列を JSON の文字列リテラルとして書く
+fn write_json_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// すべての JSON 出力の先頭に置く項目（形式の版、solver の版、コマンド名）
+pub fn header(command: &str) -> Vec<(&'static str, Json)> {
+    vec![
+        ("schema", Json::Int(SCHEMA as u64)),
+        ("solver_version", Json::str(VERSION)),
+        ("command", Json::str(command)),
+    ]
+}
+
+/// 盤面の名前、形、ブロック数、規約、毒ブロックと選べないブロック
+pub fn board_json(board: &Board) -> Json {
+    let names = |cells: &[u32]| Json::strs(cells.iter().map(|&i| board.cell_name(i)));
+    Json::Obj(vec![
+        ("name", Json::str(board.to_string())),
+        ("shape", board.dims().map_or(Json::Null, |dims| Json::Arr(dims.iter().map(|&d| Json::Int(d as u64)).collect()))),
+        ("cells", Json::Int(board.tot() as u64)),
+        ("rules", Json::str(board.ruleset().to_string())),
+        ("poison_mode", Json::str(board.poison_mode().to_string())),
+        ("poison", names(board.poison())),
+        ("forbidden", names(board.forbidden())),
+    ])
+}
+
+/// 表の集計（局面数、P 局面と N 局面の数、表のバイト数、計算時間）
+pub fn summary_json(summary: &Summary) -> Json {
+    Json::Obj(vec![
+        ("table_positions", Json::Int(summary.positions())),
+        ("p_positions", Json::Int(summary.p)),
+        ("n_positions", Json::Int(summary.n)),
+        ("table_bytes", Json::Int(summary.bytes)),
+        ("seconds", Json::Num(summary.elapsed.as_secs_f64())),
+    ])
+}
+
+/// すべての CSV 出力の先頭に並ぶ列の見出し（JSON の header と同じ形式の版と solver の版に続けて盤面）
+pub const BOARD_COLUMNS: [&str; 9] =
+    ["schema", "solver_version", "board", "shape", "cells", "rules", "poison_mode", "poison", "forbidden"];
+
+/// BOARD_COLUMNS の列の値。形は "2x3x19" のように、ブロックの並びは ";" で区切る
+pub fn board_columns(board: &Board) -> Vec<String> {
+    let names = |cells: &[u32]| cells.iter().map(|&i| board.cell_name(i)).collect::<Vec<_>>().join(";");
+    vec![
+        SCHEMA.to_string(),
+        VERSION.to_string(),
+        board.to_string(),
+        board.dims().map_or(String::new(), |dims| dims.iter().map(u32::to_string).collect::<Vec<_>>().join("x")),
+        board.tot().to_string(),
+        board.ruleset().to_string(),
+        board.poison_mode().to_string(),
+        names(board.poison()),
+        names(board.forbidden()),
+    ]
+}
+
+/// CSV の 1 行。"," や '"'、改行を含む値は '"' で囲む
+pub fn csv_row<T: AsRef<str>>(fields: &[T]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    fields.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_only_fields_that_need_it() {
+        assert_eq!(csv_row(&["a", "", "2x3"]), "a,,2x3");
+        assert_eq!(csv_row(&["(0, 0)", "say \"hi\"", "x"]), "\"(0, 0)\",\"say \"\"hi\"\"\",x");
+        assert_eq!(csv_row(&["a\nb", "c\rd", "e;f"]), "\"a\nb\",\"c\rd\",e;f");
+        assert_eq!(csv_row::<&str>(&[]), "");
+    }
+
+    #[test]
+    fn json_escapes_strings() {
+        assert_eq!(Json::str("plain").to_string(), r#""plain""#);
+        assert_eq!(Json::str("a\"b\\c").to_string(), r#""a\"b\\c""#);
+        assert_eq!(Json::str("\n\r\t\u{1}\u{1f}").to_string(), r#""\n\r\t\u0001\u001f""#);
+        assert_eq!(Json::str("毒ブロック (0, 0)").to_string(), r#""毒ブロック (0, 0)""#);
+        let obj = Json::Obj(vec![("k\"ey", Json::strs(["x", "y\\"])), ("n", Json::Num(f64::NAN))]);
+        assert_eq!(obj.to_string(), r#"{"k\"ey":["x","y\\"],"n":null}"#);
+    }
+
+    #[test]
+    fn board_columns_match_their_headers() {
+        let board = Board::new(vec![2, 3]).unwrap();
+        let columns = board_columns(&board);
+        assert_eq!(columns.len(), BOARD_COLUMNS.len());
+        assert_eq!(columns[0], SCHEMA.to_string());
+        assert_eq!(csv_row(&columns[2..5]), "2x3,2x3,6");
+    }
+}
@@ -0,0 +1,140 @@
+use std::io::{self, BufRead, Write};
+use dashmap::DashMap;
+
+use chomp::render::{render, Charset, RenderStyle};
+use chomp::{engine_move, win_with, Board, Error, Parallelism, State};
+
+const HELP: &str = "コマンド:
+  <ブロック>  そのブロックを選ぶ（箱なら \"1,0,2\" のような座標、それ以外は名前）
+  moves       合法手を一覧する
+  undo        自分の直前の手（とそれに対するエンジンの手）を取り消す
+  help        このヘルプを表示する
+  quit        対局をやめる";
+
+/// 人間とエンジンの対局を標準入出力で行う。
+/// 人間の手は legal_moves で確かめ、エンジンは必勝手があればそれを、なければ負けを延ばす手を指す。
+/// 手を指すたびに、次の手番の側が必勝か必敗かを表示する。view を指定すると局面を図でも表示する。
+/// 局面は parallelism に従って並列に解く
+pub fn play<S: State>(
+    board: &Board,
+    initial_state: S,
+    engine_first: bool,
+    view: Option<(RenderStyle, Charset)>,
+    parallelism: Parallelism,
+) -> Result<(), Error> {
+    let memo = DashMap::new();
+    let win = |state: S| win_with(board, state, &memo, parallelism, None);
+    let mut state = initial_state;
+    // 人間が手を指す前の局面の履歴（undo で戻る先）
+    let mut history: Vec<S> = Vec::new();
+    // 決着がついていれば勝敗のメッセージ
+    let mut result: Option<String> = None;
+
+    println!("盤面 {}（規約 {}）で対局します。help でコマンドを表示します", board, board.ruleset());
+    if engine_first {
+        result = engine_turn(board, &mut state, &memo, parallelism);
+    }
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        show_position(board, state, view)?;
+        if result.is_none() && board.legal_moves(state).is_empty() {
+            result = Some(no_moves_result(board, state, "あなた", "エンジン"));
+        }
+        match &result {
+            Some(msg) => println!("{}（undo で戻れます）", msg),
+            None => println!("あなたの手番です（形勢: あなたの{}）", outlook(win(state))),
+        }
+        print!("> ");
+        io::stdout().flush().map_err(|e| Error::Io(e.to_string()))?;
+        let line = match lines.next() {
+            Some(line) => line.map_err(|e| Error::Io(e.to_string()))?,
+            None => {
+                println!();
+                break;
+            }
+        };
+        match line.trim() {
+            "" => {}
+            "quit" | "q" => break,
+            "help" | "h" => println!("{}", HELP),
+            "undo" | "u" => match history.pop() {
+                Some(prev) => {
+                    state = prev;
+                    result = None;
+                }
+                None => println!("取り消せる手がありません"),
+            },
+            "moves" | "m" => {
+                let moves: Vec<&str> = board.legal_moves(state).iter().map(|&(mv, _)| board.cell_name(mv)).collect();
+                println!("合法手: {}", moves.join(" "));
+            }
+            _ if result.is_some() => println!("対局は終わっています"),
+            cell => {
+                let chosen = match board.parse_cell(cell) {
+                    Ok(chosen) => chosen,
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+                if board.poisoned_moves(state).contains(&chosen) {
+                    history.push(state);
+                    result = Some("毒を食べたので、あなたの負けです".to_string());
+                    continue;
+                }
+                let Some(&(_, new_state)) = board.legal_moves(state).iter().find(|&&(mv, _)| mv == chosen) else {
+                    println!("{} は合法手ではありません", board.cell_name(chosen));
+                    continue;
+                };
+                history.push(state);
+                state = new_state;
+                println!("あなたの手: {}（形勢: エンジンの{}）", board.cell_name(chosen), outlook(win(state)));
+                result = engine_turn(board, &mut state, &memo, parallelism);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// エンジンが一手指す。合法手がなければ勝敗のメッセージを返す
+fn engine_turn<S: State>(
+    board: &Board,
+    state: &mut S,
+    memo: &DashMap<S, bool>,
+    parallelism: Parallelism,
+) -> Option<String> {
+    let Some(mv) = engine_move(board, *state, memo, parallelism) else {
+        return Some(no_moves_result(board, *state, "エンジン", "あなた"));
+    };
+    *state = *state & !board.removal_mask::<S>(mv);
+    println!("エンジンの手: {}", board.cell_name(mv));
+    None
+}
+
+/// 局面 state で手番の側 player に合法手がないときの勝敗のメッセージ
+fn no_moves_result<S: State>(board: &Board, state: S, player: &str, opponent: &str) -> String {
+    if !board.poisoned_moves(state).is_empty() {
+        return format!("{}には毒を食べる手しか残っていません。{}の勝ちです", player, opponent);
+    }
+    let winner = if board.wins_without_moves(state) { player } else { opponent };
+    format!("{}に指せる手がありません。{}の勝ちです", player, winner)
+}
+
+fn outlook(winning: bool) -> &'static str {
+    if winning { "必勝" } else { "必敗" }
+}
+
+/// 局面を、箱の盤面なら高さ行列（と view を指定していれば図）で、それ以外なら残っているブロックの並びで表示する
+fn show_position<S: State>(board: &Board, state: S, view: Option<(RenderStyle, Charset)>) -> Result<(), Error> {
+    if board.dims().is_some() {
+        println!("局面（高さ行列）: {}", board.state_to_heights(state)?);
+        if let Some((style, charset)) = view {
+            print!("{}", render(board, state, S::zero(), style, charset)?);
+        }
+    } else {
+        let cells: Vec<&str> = (0..board.tot()).filter(|&i| state.has(i)).map(|i| board.cell_name(i)).collect();
+        println!("残っているブロック: {}", cells.join(" "));
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use crate::board::Coord;
+use crate::error::Error;
+use crate::state::{cell_words, State};
+
+/// 有限半順序集合。要素を 0..len のインデックスで表し、各要素以上の要素の集合（上集合）を持つ。
+/// Chomp で要素を選ぶと、その要素の上集合に含まれる残りの要素がすべて取り除かれる。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Poset {
+    labels: Vec<String>,
+    /// up[i] = i 以上の要素（i 自身を含む）のインデックス
+    up: Vec<Vec<u32>>,
+    /// up_words[i * w..(i + 1) * w] = up[i] を State::from_words の語の並びにしたもの（w は 1 要素あたりの語数）
+    up_words: Vec<u64>,
+}
+
+impl Poset {
+    /// 要素の名前と被覆関係（Hasse 図の辺）から半順序集合を作る。
+    /// 辺 (a, b) は a < b で b が a を被覆することを表す。辺に閉路があればエラー
+    pub fn new(labels: Vec<String>, covers: &[(u32, u32)]) -> Result<Poset, Error> {
+        let n = labels.len();
+        let mut succ = vec![Vec::new(); n];
+        let mut indegree = vec![0usize; n];
+        for &(a, b) in covers {
+            if a as usize >= n || b as usize >= n {
+                return Err(Error::InvalidPoset(format!("辺 ({}, {}) が要素数 {} を超えています", a, b, n)));
+            }
+            succ[a as usize].push(b);
+            indegree[b as usize] += 1;
+        }
+
+        // 位相順序を求め、閉路がないことを確かめる
+        let mut order: Vec<usize> = (0..n).filter(|&i| indegree[i] == 0).collect();
+        let mut head = 0;
+        while head < order.len() {
+            let i = order[head];
+            head += 1;
+            for &j in &succ[i] {
+                indegree[j as usize] -= 1;
+                if indegree[j as usize] == 0 {
+                    order.push(j as usize);
+                }
+            }
+        }
+        if order.len() < n {
+            return Err(Error::InvalidPoset("被覆関係に閉路があります".to_string()));
+        }
+
+        // 位相順序の逆順に、後続の上集合をまとめて上集合を作る
+        let mut up: Vec<Vec<u32>> = vec![Vec::new(); n];
+        for &i in order.iter().rev() {
+            let mut set = vec![i as u32];
+            for &j in &succ[i] {
+                set.extend_from_slice(&up[j as usize]);
+            }
+            set.sort_unstable();
+            set.dedup();
+            up[i] = set;
+        }
+        let up_words = up.iter().flat_map(|set| cell_words(n as u32, set.iter().copied())).collect();
+        Ok(Poset { labels, up, up_words })
+    }
+
+    /// 各辺の長さが dims の箱に成分ごとの大小で順序を入れたもの（直積順序）。
+    /// 要素のインデックスは第 0 軸が最も速く変わる混合基数表記で、名前は "(x, y, z)"
+    pub fn product(dims: &[u32]) -> Poset {
+        let n: u32 = dims.iter().product();
+        let mut labels = Vec::with_capacity(n as usize);
+        let mut covers = Vec::new();
+        for i in 0..n {
+            let coord = Coord::from_index(dims, i);
+            let mut stride = 1;
+            for (&c, &d) in coord.0.iter().zip(dims) {
+                // 各軸方向に一つ進んだ要素が i を被覆する
+                if c + 1 < d {
+                    covers.push((i, i + stride));
+                }
+                stride *= d;
+            }
+            labels.push(coord.to_string());
+        }
+        Poset::new(labels, &covers).unwrap()
+    }
+
+    /// Hasse 図のテキストから半順序集合を作る。各行は次のいずれか（# 以降はコメント）:
+    ///   a b   … a < b で b が a を被覆する
+    ///   a     … 要素 a（他の要素と比較できない要素を書くため）
+    /// 要素は最初に現れた順にインデックスが振られる
+    pub fn parse(text: &str) -> Result<Poset, Error> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut index: HashMap<String, u32> = HashMap::new();
+        let mut covers = Vec::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            let mut ids = Vec::new();
+            for name in line.split_whitespace() {
+                let id = *index.entry(name.to_string()).or_insert_with(|| {
+                    labels.push(name.to_string());
+                    labels.len() as u32 - 1
+                });
+                ids.push(id);
+            }
+            match ids[..] {
+                [] | [_] => {}
+                [a, b] => covers.push((a, b)),
+                _ => {
+                    return Err(Error::InvalidPoset(format!(
+                        "{} 行目: 1 行には要素を 1 つか 2 つ書いてください",
+                        lineno + 1
+                    )))
+                }
+            }
+        }
+        if labels.is_empty() {
+            return Err(Error::InvalidPoset("要素がありません".to_string()));
+        }
+        Poset::new(labels, &covers)
+    }
+
+    /// 要素の名前を付け替える。順序は変えない
+    pub fn relabel(&mut self, labels: Vec<String>) {
+        assert_eq!(labels.len(), self.labels.len());
+        self.labels = labels;
+    }
+
+    /// 要素数
+    pub fn len(&self) -> u32 {
+        self.labels.len() as u32
+    }
+
+    /// 要素がないか
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// 要素 i の名前
+    pub fn label(&self, i: u32) -> &str {
+        &self.labels[i as usize]
+    }
+
+    /// 名前から要素のインデックスを引く
+    pub fn index_of(&self, label: &str) -> Option<u32> {
+        self.labels.iter().position(|l| l == label).map(|i| i as u32)
+    }
+
+    /// 要素 i 以上の要素（i 自身を含む）
+    pub fn up_set(&self, i: u32) -> &[u32] {
+        &self.up[i as usize]
+    }
+
+    /// 要素 i 以上の要素（i 自身を含む）の集合を状態にしたもの
+    pub fn up_mask<S: State>(&self, i: u32) -> S {
+        let w = self.len().div_ceil(64) as usize;
+        S::from_words(&self.up_words[i as usize * w..(i as usize + 1) * w])
+    }
+
+    /// 要素の名前と順序から決まる 64 ビットの指紋（FNV-1a）。
+    /// 同じ名前・同じ順序の半順序集合は、どのファイルから読んでも同じ値になる
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                hash = (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        for (label, up) in self.labels.iter().zip(&self.up) {
+            feed(&(label.len() as u32).to_le_bytes());
+            feed(label.as_bytes());
+            feed(&(up.len() as u32).to_le_bytes());
+            up.iter().for_each(|j| feed(&j.to_le_bytes()));
+        }
+        hash
+    }
+
+    /// 極小元（自分より小さい要素を持たない要素）
+    pub fn minimal(&self) -> Vec<u32> {
+        let mut has_lower = vec![false; self.labels.len()];
+        for (i, up) in self.up.iter().enumerate() {
+            for &j in up {
+                if j as usize != i {
+                    has_lower[j as usize] = true;
+                }
+            }
+        }
+        (0..self.len()).filter(|&i| !has_lower[i as usize]).collect()
+    }
+}
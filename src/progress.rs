@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::memo::Memo;
+use crate::state::State;
+
+/// スレッドごとの数。他のスレッドの数と同じキャッシュラインに載らないよう揃える
+#[derive(Default)]
+#[repr(align(128))]
+struct Counters {
+    /// 探索で勝敗を求めた局面の数
+    solved: AtomicU64,
+    /// 最後に並列に分けた局面の深さ（初期局面からの手数）
+    split_depth: AtomicU32,
+    /// これまでに並列に分けた最も深い局面の深さ
+    max_split_depth: AtomicU32,
+}
+
+/// 長い探索の進み具合。探索から数を書き込み、別スレッドの report_every が定期的に表示する。
+/// 探索のスレッドどうしが同じ値を奪い合わないよう、数はスレッドごとに持って表示のときに集める
+pub struct Progress {
+    start: Instant,
+    /// 盤面の order ideal の総数（分かれば）。解いた局面数の目安にする
+    total: Option<u64>,
+    /// rayon のスレッドごとの数と、最後にそれ以外のスレッドの分
+    counters: Vec<Counters>,
+    done: AtomicBool,
+}
+
+impl Progress {
+    pub fn new(total: Option<u64>) -> Progress {
+        Progress {
+            start: Instant::now(),
+            total,
+            counters: (0..=rayon::current_num_threads()).map(|_| Counters::default()).collect(),
+            done: AtomicBool::new(false),
+        }
+    }
+
+    /// 呼び出したスレッドの分の数
+    fn counters(&self) -> &Counters {
+        let i = rayon::current_thread_index().unwrap_or(self.counters.len() - 1);
+        &self.counters[i.min(self.counters.len() - 1)]
+    }
+
+    /// 局面を 1 つ解いたことを記録する
+    pub fn record_solved(&self) {
+        self.counters().solved.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 局面を count 個まとめて解いたことを記録する（後退解析の区切りごとなど）
+    pub fn record_solved_batch(&self, count: u64) {
+        self.counters().solved.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// 深さ depth の局面の手を並列に調べ始めたことを記録する
+    pub fn record_split(&self, depth: u32) {
+        let c = self.counters();
+        c.split_depth.store(depth, Ordering::Relaxed);
+        c.max_split_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    /// 探索を始めてからの経過時間
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// 探索で勝敗を求めた局面の数
+    pub fn solved(&self) -> u64 {
+        self.counters.iter().map(|c| c.solved.load(Ordering::Relaxed)).sum()
+    }
+
+    /// 各スレッドが最後に並列に分けた深さのうち最も深いものと、これまでで最も深いもの
+    fn split_depths(&self) -> (u32, u32) {
+        let max = |f: fn(&Counters) -> &AtomicU32| {
+            self.counters.iter().map(|c| f(c).load(Ordering::Relaxed)).max().unwrap_or(0)
+        };
+        (max(|c| &c.split_depth), max(|c| &c.max_split_depth))
+    }
+
+    /// 探索が終わったことを知らせ、report_every を止める
+    pub fn finish(&self) {
+        self.done.store(true, Ordering::Relaxed);
+    }
+
+    /// finish が呼ばれるまで、interval ごとに進み具合を標準エラー出力に表示する
+    pub fn report_every<S: State, M: Memo<S>>(&self, interval: Duration, memo: &M) {
+        let mut last = (Instant::now(), 0u64);
+        let mut next = Instant::now() + interval;
+        while !self.done.load(Ordering::Relaxed) {
+            // 終了にすぐ気づけるよう、短く区切って待つ
+            thread::sleep(Duration::from_millis(50).min(interval));
+            if Instant::now() < next {
+                continue;
+            }
+            next += interval;
+            let solved = self.solved();
+            let rate = (solved - last.1) as f64 / last.0.elapsed().as_secs_f64();
+            last = (Instant::now(), solved);
+            let estimate = match self.total {
+                Some(total) => format!(" / 全局面 {}（{:.1}%）", total, 100.0 * solved as f64 / total as f64),
+                None => String::new(),
+            };
+            // 後退解析のように並列に分けない計算では深さを表示しない
+            let split = match self.split_depths() {
+                (_, 0) => String::new(),
+                (depth, max) => format!("、並列に分けた深さ {}（最大 {}）", depth, max),
+            };
+            eprintln!(
+                "[{:.0} 秒] 解いた局面 {}{}、{:.0} 局面/秒、表 {} 局面（約 {}）{}",
+                self.elapsed().as_secs_f64(),
+                solved,
+                estimate,
+                rate,
+                memo.entries(),
+                format_bytes(memo.approx_bytes()),
+                split
+            );
+        }
+    }
+}
+
+/// バイト数を "12.3 MiB" のように読みやすく表す
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", value, UNITS[unit]) }
+}
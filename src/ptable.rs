@@ -0,0 +1,164 @@
+use std::io::Write;
+
+use rayon::prelude::*;
+
+use crate::board::Board;
+use crate::db::{board_key, decode_state, encode_state, open, read_bytes, read_header, read_u64, state_len, write_atomically, write_header};
+use crate::error::Error;
+use crate::memo::Memo;
+use crate::rank::Ranker;
+use crate::state::State;
+use crate::VERSION as SOLVER_VERSION;
+
+/// バイナリ形式のファイルの先頭に置く識別子
+const MAGIC: &[u8; 8] = b"CHOMPPT\0";
+/// バイナリ形式の版。形式を変えたら上げる
+const VERSION: u32 = 2;
+
+/// 盤面のすべての P 局面（手番の側が負ける局面）の表。
+/// 局面は残っているブロック数の少ない順に、同じブロック数の中では順位の順に並べる
+pub struct PTable<S> {
+    positions: Vec<S>,
+    /// counts[k] = ブロックが k 個残っている P 局面の数
+    counts: Vec<u64>,
+}
+
+impl<S: State> PTable<S> {
+    /// 盤面のすべての局面を解き終えた表 memo（後退解析の表など）から P 局面を集める。
+    /// 表に解いていない局面があればエラー
+    pub fn from_memo<M: Memo<S>>(board: &Board, ranker: &Ranker, memo: &M) -> Result<PTable<S>, Error> {
+        // ranker は order ideal を並べるので、毒ブロックを含まない（局面でない）ものは表になくてよい
+        let mut positions: Vec<S> = (0..ranker.count())
+            .into_par_iter()
+            .map(|r| ranker.unrank::<S>(r))
+            .filter_map(|state| match memo.get(&state) {
+                Some(win) => (!win).then_some(Ok(state)),
+                None if board.contains_poison(state) => Some(Err(())),
+                None => None,
+            })
+            .collect::<Result<_, ()>>()
+            .map_err(|()| {
+                Error::InvalidOutput(format!("盤面 {} に解いていない局面があるので、P 局面の表を作れません", board))
+            })?;
+        // 安定なソートなので、同じブロック数の中では順位の順のまま
+        positions.sort_by_key(|state| state.count_ones());
+        let mut counts = vec![0; board.tot() as usize + 1];
+        for state in &positions {
+            counts[state.count_ones() as usize] += 1;
+        }
+        Ok(PTable { positions, counts })
+    }
+
+    /// P 局面の並び
+    pub fn positions(&self) -> &[S] {
+        &self.positions
+    }
+
+    /// 残っているブロック数ごとの P 局面の数（添字がブロック数）
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// 表をバイナリ形式で path に書き出す。形式（整数はすべてリトルエンディアン）:
+    ///   MAGIC (8 バイト), VERSION (u32),
+    ///   盤面の鍵の長さ (u32), 盤面の鍵 (UTF-8。db::board_key と同じ),
+    ///   ブロック数 tot (u32), ブロック数 0..=tot ごとの P 局面数 (u64 × (tot + 1)), P 局面数 (u64),
+    ///   局面ごとに: 状態 (ceil(tot / 8) バイト。ブロック i がバイト i / 8 の第 i % 8 ビット)
+    pub fn write_binary(&self, path: &str, board: &Board) -> Result<(), Error> {
+        write_atomically(path, |out| {
+            write_header(out, board, MAGIC, VERSION)?;
+            for &count in &self.counts {
+                out.write_all(&count.to_le_bytes())?;
+            }
+            out.write_all(&(self.positions.len() as u64).to_le_bytes())?;
+            let mut record = vec![0u8; state_len(board)];
+            for &state in &self.positions {
+                encode_state(board, state, &mut record);
+                out.write_all(&record)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// write_binary で書き出した表を読む。見出しが board のものでないか、ブロック数ごとの数が
+    /// 局面と合わないか、局面として正しくない状態があればエラー
+    pub fn read_binary(path: &str, board: &Board) -> Result<PTable<S>, Error> {
+        let mut input = open(path)?;
+        read_header(&mut input, path, board, MAGIC, VERSION, "P 局面の表")?;
+        let counts = (0..=board.tot()).map(|_| read_u64(&mut input, path)).collect::<Result<Vec<_>, _>>()?;
+        let len = read_u64(&mut input, path)?;
+        let mut positions = Vec::new();
+        let mut found = vec![0; counts.len()];
+        for _ in 0..len {
+            let state: S = decode_state(board, &read_bytes(&mut input, path, state_len(board))?);
+            board.check_position(state)?;
+            found[state.count_ones() as usize] += 1;
+            positions.push(state);
+        }
+        if found != counts {
+            return Err(Error::InvalidDatabase(format!("{} のブロック数ごとの局面数が局面と一致しません", path)));
+        }
+        Ok(PTable { positions, counts })
+    }
+
+    /// 表をテキスト形式で path に書き出す。# で始まる見出しの行（盤面の鍵、solver の版、
+    /// ブロック数ごとの P 局面数）に続けて、1 行に 1 局面を高さ行列で書く。
+    /// 各行はそのまま --position に渡せる。箱でない盤面はエラー
+    pub fn write_text(&self, path: &str, board: &Board) -> Result<(), Error> {
+        let lines = self
+            .positions
+            .iter()
+            .map(|&state| board.state_to_heights(state).map(|h| h.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        write_atomically(path, |out| {
+            writeln!(out, "# P 局面の表: {} {}", board, board_key(board))?;
+            writeln!(out, "# solver の版: {}", SOLVER_VERSION)?;
+            writeln!(out, "# P 局面数: {}", self.positions.len())?;
+            writeln!(out, "# 残りブロック数ごとの P 局面数（ブロック数 局面数）:")?;
+            for (cells, count) in self.counts.iter().enumerate() {
+                writeln!(out, "# {} {}", cells, count)?;
+            }
+            for line in &lines {
+                writeln!(out, "{}", line)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::retrograde;
+
+    fn table(dims: &[u32]) -> (Board, PTable<u128>) {
+        let board = Board::new(dims.to_vec()).unwrap();
+        let memo = retrograde::<u128>(&board, Ranker::new(&board).unwrap());
+        let table = PTable::from_memo(&board, memo.ranker(), &memo).unwrap();
+        (board, table)
+    }
+
+    #[test]
+    fn counts_p_positions_of_2x2x3() {
+        let (_, table) = table(&[2, 2, 3]);
+        assert_eq!(table.positions().len(), 9);
+        assert_eq!(table.counts().iter().sum::<u64>(), 9);
+        // 毒ブロックだけが残った局面は P 局面
+        assert_eq!(table.counts()[1], 1);
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        let (board, table) = table(&[2, 2, 3]);
+        let path = std::env::temp_dir().join(format!("chomp-ptable-{}.ptable", std::process::id()));
+        let path = path.to_string_lossy();
+        table.write_binary(&path, &board).unwrap();
+        let read = PTable::<u128>::read_binary(&path, &board).unwrap();
+        assert_eq!(read.counts(), table.counts());
+        assert_eq!(read.positions(), table.positions());
+        // 別の盤面の表としては読めない
+        let other = Board::new(vec![2, 3, 2]).unwrap();
+        assert!(matches!(PTable::<u128>::read_binary(&path, &other), Err(Error::InvalidDatabase(_))));
+        std::fs::remove_file(&*path).unwrap();
+    }
+}
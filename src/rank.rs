@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use crate::board::Board;
+use crate::error::Error;
+use crate::heights::Heights;
+use crate::state::State;
+
+/// 順位付けの表（層ごとの部分層の一覧と減少列の個数の累積和）に使ってよいメモリの上限（バイト）
+const MAX_TABLE_BYTES: u64 = 1 << 30;
+
+/// 盤面の order ideal と 0..count の間の全単射（完全ランキング）。
+///
+/// order ideal を最後の軸方向の層 L_0 ⊇ L_1 ⊇ ... ⊇ L_{n-1} に分けると、各層は
+/// 最後の軸以外からなる格子の order ideal になる。格子の order ideal を列挙して番号を振り、
+/// 「層 k 以下から始まる長さ r の減少列の個数」を数えておけば、層の列を辞書式順序で
+/// 数え上げることで順位を求められる（3 次元なら箱に収まる plane partition の数え上げ）。
+/// 累積和は各層に含まれる層についてだけ持つので、表の大きさは層の組 L ⊇ L' の数に比例する
+#[derive(Clone)]
+pub struct Ranker {
+    board: Board,
+    lattice: Lattice,
+    /// subs[starts[p]..starts[p + 1]] = 層 p に含まれる層の番号（昇順）
+    starts: Vec<usize>,
+    subs: Vec<u32>,
+    /// prefix[r - 1][starts[p] + i] = 層 p に含まれる層のうち i 番目より前の層 j について、
+    /// j 以下から始まる長さ r の減少列の個数を足したもの（1 <= r < height - 1）
+    prefix: Vec<Vec<u64>>,
+    /// 最上段（r = height - 1）の累積和。この段の上は常に格子全体なので、層の番号で引く
+    top: Vec<u64>,
+    count: u64,
+}
+
+impl Ranker {
+    /// 盤面の order ideal を数え上げ、順位付けの表を作る。格子が 64 ブロックを超えるか、
+    /// 表が MAX_TABLE_BYTES を超えるか、order ideal の数が u64 に収まらなければエラー
+    pub fn new(board: &Board) -> Result<Ranker, Error> {
+        let lattice = Lattice::new(board)?;
+        let (k, height) = (lattice.layers.len(), lattice.height as usize);
+        // 部分層 1 つあたり、番号（4 バイト）と中段の累積和（8 バイト × 段数）を持つ
+        let per_pair = 4 + 8 * height.saturating_sub(2) as u64;
+        let mut starts = vec![0];
+        let mut subs = Vec::new();
+        let mut f = vec![vec![1u64; k]; height];
+        for p in 0..k {
+            let first = subs.len();
+            lattice.sub_layers(p, &mut subs);
+            let bytes = (subs.len() as u64).saturating_mul(per_pair).saturating_add(lattice.approx_bytes());
+            if bytes > MAX_TABLE_BYTES {
+                return Err(Error::TooManyPositions(format!(
+                    "盤面 {} の順位付けの表が {} バイトを超えます",
+                    board, MAX_TABLE_BYTES
+                )));
+            }
+            starts.push(subs.len());
+            lattice.extend_counts(board, &mut f, p, &subs[first..])?;
+        }
+
+        let prefix = (1..height.saturating_sub(1))
+            .map(|r| {
+                let mut row = vec![0u64; subs.len()];
+                for p in 0..k {
+                    let mut acc = 0u64;
+                    for i in starts[p]..starts[p + 1] {
+                        row[i] = acc;
+                        // 合計は f[r + 1][p] 以下なので溢れない
+                        acc += f[r][subs[i] as usize];
+                    }
+                }
+                row
+            })
+            .collect();
+        let top = if height >= 2 {
+            f[height - 1].iter().scan(0u64, |acc, &n| Some(std::mem::replace(acc, *acc + n))).collect()
+        } else {
+            Vec::new()
+        };
+        let count = lattice.total(board, &f)?;
+        Ok(Ranker { board: board.clone(), lattice, starts, subs, prefix, top, count })
+    }
+
+    /// 順位付けの表を作らずに盤面の order ideal の総数だけを数える。
+    /// 使うメモリは層の数と高さの積に比例する程度で済む。箱でない盤面や数が u64 に収まらなければエラー
+    pub fn count_positions(board: &Board) -> Result<u64, Error> {
+        let lattice = Lattice::new(board)?;
+        let k = lattice.layers.len();
+        let mut f = vec![vec![1u64; k]; lattice.height as usize];
+        let mut subs = Vec::new();
+        for p in 0..k {
+            subs.clear();
+            lattice.sub_layers(p, &mut subs);
+            lattice.extend_counts(board, &mut f, p, &subs)?;
+        }
+        lattice.total(board, &f)
+    }
+
+    /// 盤面の order ideal の総数
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// 順位付けする盤面
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// 順位付けの表のおおよそのバイト数
+    pub fn approx_bytes(&self) -> u64 {
+        let words = self.prefix.iter().map(Vec::len).sum::<usize>() + self.top.len() + self.starts.len();
+        (words * 8 + self.subs.len() * 4) as u64 + self.lattice.approx_bytes()
+    }
+
+    /// 層 prev に含まれる層のうち i 番目より前の層 j について、j 以下から始まる長さ r の減少列の個数の和。
+    /// 長さ 0 の列はどの層からも 1 通りなので、r = 0 なら i そのもの
+    fn prefix_at(&self, r: usize, prev: usize, i: usize) -> u64 {
+        if r == 0 {
+            i as u64
+        } else if r == self.lattice.height as usize - 1 {
+            self.top[i]
+        } else {
+            self.prefix[r - 1][self.starts[prev] + i]
+        }
+    }
+
+    /// 層 prev に含まれる層の番号
+    fn subs_of(&self, prev: usize) -> &[u32] {
+        &self.subs[self.starts[prev]..self.starts[prev + 1]]
+    }
+
+    /// 状態の順位を返す。状態が order ideal でなければ None
+    pub fn rank<S: State>(&self, state: S) -> Option<u64> {
+        let Lattice { base, height, .. } = self.lattice;
+        let mut prev = self.lattice.layers.len() - 1;
+        let mut rank = 0;
+        for z in 0..height {
+            let mut mask = 0u64;
+            for b in 0..base {
+                if state.has(b + z * base) {
+                    mask |= 1 << b;
+                }
+            }
+            let l = *self.lattice.index.get(&mask)?;
+            // 一つ下の層に含まれていなければ order ideal ではない
+            let i = self.subs_of(prev).binary_search(&(l as u32)).ok()?;
+            rank += self.prefix_at((height - 1 - z) as usize, prev, i);
+            prev = l;
+        }
+        Some(rank)
+    }
+
+    /// 順位から状態を復元する（rank の逆写像）。rank は count 未満であること
+    pub fn unrank<S: State>(&self, mut rank: u64) -> S {
+        let height = self.lattice.height as usize;
+        let mut prev = self.lattice.layers.len() - 1;
+        let mut heights = vec![0u32; self.lattice.base as usize];
+        for z in 0..height {
+            let r = height - 1 - z;
+            // 累積和は i について狭義単調増加なので、rank 以下となる最後の部分層を選ぶ
+            let (mut lo, mut hi) = (0, self.subs_of(prev).len());
+            while hi - lo > 1 {
+                let mid = (lo + hi) / 2;
+                if self.prefix_at(r, prev, mid) <= rank {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            let i = lo;
+            rank -= self.prefix_at(r, prev, i);
+            let l = self.subs_of(prev)[i] as usize;
+            for (b, h) in heights.iter_mut().enumerate() {
+                if self.lattice.layers[l] & (1 << b) != 0 {
+                    *h += 1;
+                }
+            }
+            prev = l;
+        }
+        let heights = Heights::new(self.board.dims().unwrap()[0], heights);
+        self.board.heights_to_state(&heights).unwrap()
+    }
+}
+
+/// 最後の軸以外からなる格子と、その order ideal（層）の一覧
+#[derive(Clone)]
+struct Lattice {
+    base_dims: Vec<u32>,
+    /// 柱の本数（格子のブロック数）
+    base: u32,
+    /// 最後の軸方向のブロック数
+    height: u32,
+    /// 層をビットマスクで表したもの。ブロック数の少ない順（同じならマスクの順）なので、
+    /// 先頭が空集合、末尾が格子全体で、層に含まれる層はその層より前にある
+    layers: Vec<u64>,
+    index: HashMap<u64, usize>,
+}
+
+impl Lattice {
+    /// 箱の盤面の格子の層を列挙する。箱でないか、格子が 64 ブロックを超えればエラー
+    fn new(board: &Board) -> Result<Lattice, Error> {
+        let dims = board.dims().ok_or_else(|| {
+            Error::InvalidShape(format!("盤面 {} は箱ではないので順位付けできません", board))
+        })?;
+        let (&height, base_dims) = dims.split_last().unwrap();
+        let base = board.tot() / height;
+        if base > 64 {
+            return Err(Error::TooManyPositions(format!(
+                "柱の本数 {} が順位付けで扱える 64 本を超えています",
+                base
+            )));
+        }
+        let mut layers = Vec::new();
+        collect_layers(base_dims, base, 0, 0, u64::MAX, &mut layers);
+        layers.sort_by_key(|&m| (m.count_ones(), m));
+        let index = layers.iter().enumerate().map(|(i, &m)| (m, i)).collect();
+        let lattice = Lattice { base_dims: base_dims.to_vec(), base, height, layers, index };
+        if lattice.approx_bytes() > MAX_TABLE_BYTES {
+            return Err(Error::TooManyPositions(format!(
+                "盤面 {} の層の数 {} が多すぎて順位付けできません",
+                board,
+                lattice.layers.len()
+            )));
+        }
+        Ok(lattice)
+    }
+
+    /// 層の一覧と、数え上げに使う段ごとの個数 f のおおよそのバイト数
+    fn approx_bytes(&self) -> u64 {
+        // 層 1 つあたり、マスクとハッシュ表の項目（約 24 バイト）、段ごとの f と最上段の累積和
+        (self.layers.len() as u64).saturating_mul(32 + 8 * (self.height as u64 + 1))
+    }
+
+    /// 層 p に含まれる層の番号を昇順に out の末尾に足す
+    fn sub_layers(&self, p: usize, out: &mut Vec<u32>) {
+        let mut masks = Vec::new();
+        collect_layers(&self.base_dims, self.base, 0, 0, self.layers[p], &mut masks);
+        let first = out.len();
+        out.extend(masks.iter().map(|m| self.index[m] as u32));
+        out[first..].sort_unstable();
+    }
+
+    /// 層 p に含まれる層 subs から f[r][p] = Σ_{j ⊆ p} f[r - 1][j] を r = 1 から順に求める。
+    /// 層 p に含まれる層は p より前にあるので、p を小さい順に処理すれば必要な値は揃っている
+    fn extend_counts(&self, board: &Board, f: &mut [Vec<u64>], p: usize, subs: &[u32]) -> Result<(), Error> {
+        for r in 1..f.len() {
+            let mut sum = 0u64;
+            for &j in subs {
+                sum = sum.checked_add(f[r - 1][j as usize]).ok_or_else(|| overflow(board))?;
+            }
+            f[r][p] = sum;
+        }
+        Ok(())
+    }
+
+    /// 格子全体以下から始まる長さ height の減少列、つまり盤面の order ideal の総数
+    fn total(&self, board: &Board, f: &[Vec<u64>]) -> Result<u64, Error> {
+        f[self.height as usize - 1]
+            .iter()
+            .try_fold(0u64, |acc, &n| acc.checked_add(n))
+            .ok_or_else(|| overflow(board))
+    }
+}
+
+fn overflow(board: &Board) -> Error {
+    Error::TooManyPositions(format!("盤面 {} の局面数が u64 に収まりません", board))
+}
+
+/// 格子の order ideal のうち within に含まれるものを深さ優先で列挙する。インデックス順に各ブロックを
+/// 入れるか決め、within に含まれ、各軸方向の一つ手前のブロックがすべて入っているときだけ入れられる
+fn collect_layers(base_dims: &[u32], base: u32, b: u32, mask: u64, within: u64, out: &mut Vec<u64>) {
+    if b == base {
+        out.push(mask);
+        return;
+    }
+    collect_layers(base_dims, base, b + 1, mask, within, out);
+    if within & (1 << b) == 0 {
+        return;
+    }
+    let mut stride = 1;
+    let mut rest = b;
+    for &d in base_dims {
+        if !rest.is_multiple_of(d) && mask & (1 << (b - stride)) == 0 {
+            return;
+        }
+        rest /= d;
+        stride *= d;
+    }
+    collect_layers(base_dims, base, b + 1, mask | (1 << b), within, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Ruleset;
+
+    fn board(dims: &[u32]) -> Board {
+        Board::new(dims.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn count_matches_macmahon() {
+        // 箱 a×b×c に収まる plane partition の数
+        for (dims, expected) in [([2, 2, 2], 20), ([3, 3, 3], 980), ([4, 4, 4], 232848)] {
+            let b = board(&dims);
+            assert_eq!(Ranker::new(&b).unwrap().count(), expected);
+            assert_eq!(Ranker::count_positions(&b).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn count_matches_brute_force() {
+        // 毒ブロックのない規約なら、局面として正しいことと order ideal であることが同じ
+        for dims in [&[5][..], &[3, 4], &[2, 2, 3], &[2, 1, 3], &[2, 2, 2, 2]] {
+            let b = board(dims).with_ruleset(Ruleset::Normal);
+            let ideals = (0u128..1 << b.tot()).filter(|&s| b.check_position(s).is_ok()).count() as u64;
+            assert_eq!(Ranker::new(&b).unwrap().count(), ideals, "{:?}", dims);
+        }
+    }
+
+    #[test]
+    fn rank_and_unrank_are_inverse() {
+        for dims in [&[5][..], &[3, 4], &[2, 3, 4], &[3, 3, 3], &[2, 2, 2, 3]] {
+            let b = board(dims);
+            let ranker = Ranker::new(&b).unwrap();
+            for r in 0..ranker.count() {
+                let state: u128 = ranker.unrank(r);
+                assert_eq!(ranker.rank(state), Some(r), "{:?}", dims);
+                assert_eq!(ranker.unrank::<u128>(ranker.rank(state).unwrap()), state);
+            }
+        }
+    }
+
+    #[test]
+    fn rank_rejects_non_ideals() {
+        let b = board(&[2, 3, 4]);
+        let ranker = Ranker::new(&b).unwrap();
+        // 原点のない状態や、宙に浮いたブロックを含む状態
+        let full: u128 = b.full_state();
+        assert_eq!(ranker.rank(full & !1), None);
+        assert_eq!(ranker.rank(1u128 | 1 << 7), None);
+    }
+
+    #[test]
+    fn large_tables_are_rejected_up_front() {
+        assert!(matches!(Ranker::new(&board(&[8, 8, 16])), Err(Error::TooManyPositions(_))));
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::board::{Board, Coord};
+use crate::error::Error;
+use crate::state::State;
+
+/// 局面の描き方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderStyle {
+    /// 最後の軸で切った層ごとに、第 0 軸を横、第 1 軸を縦にしてブロックを並べる
+    Layers,
+    /// 最後の軸方向の柱の高さを、残りの軸の格子に並べる
+    HeightMap,
+}
+
+impl FromStr for RenderStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<RenderStyle, Error> {
+        match s {
+            "layers" => Ok(RenderStyle::Layers),
+            "heights" => Ok(RenderStyle::HeightMap),
+            _ => Err(Error::InvalidShape(format!("描き方は layers か heights のいずれかです: {}", s))),
+        }
+    }
+}
+
+/// 描画に使う文字の種類
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Charset {
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+impl Charset {
+    /// (ブロック, 空き, 毒ブロック, 選べないブロック, 手で取り除かれるブロック) の記号
+    fn symbols(self) -> [&'static str; 5] {
+        match self {
+            Charset::Unicode => ["■", "·", "●", "□", "×"],
+            Charset::Ascii => ["#", ".", "P", "F", "x"],
+        }
+    }
+
+    /// 高さ行列で手を指した後の高さを示す矢印
+    fn arrow(self) -> &'static str {
+        match self {
+            Charset::Unicode => "→",
+            Charset::Ascii => ">",
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Charset, Error> {
+        match s {
+            "unicode" => Ok(Charset::Unicode),
+            "ascii" => Ok(Charset::Ascii),
+            _ => Err(Error::InvalidShape(format!("文字の種類は unicode か ascii のいずれかです: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [block, empty, poison, forbidden, removed] = self.symbols();
+        write!(
+            f,
+            "{} ブロック  {} 空き  {} 毒  {} 選べない  {} 手で取り除かれる",
+            block, empty, poison, forbidden, removed
+        )
+    }
+}
+
+/// 箱の盤面の局面 state を文字で描く。removed に含まれるブロック（候補手の removal_mask など）は
+/// 取り除かれるブロックとして強調する。強調しないなら S::zero() を渡す。箱でない盤面はエラー
+pub fn render<S: State>(
+    board: &Board,
+    state: S,
+    removed: S,
+    style: RenderStyle,
+    charset: Charset,
+) -> Result<String, Error> {
+    let dims = board
+        .dims()
+        .ok_or_else(|| Error::InvalidShape(format!("盤面 {} は箱ではないので描けません", board)))?;
+    match style {
+        RenderStyle::Layers => Ok(render_layers(board, dims, state, removed, charset)),
+        RenderStyle::HeightMap => render_height_map(board, dims, state, removed, charset),
+    }
+}
+
+fn render_layers<S: State>(board: &Board, dims: &[u32], state: S, removed: S, charset: Charset) -> String {
+    let [block, empty, poison, forbidden, removed_mark] = charset.symbols();
+    let cells: Vec<String> = (0..board.tot())
+        .map(|i| {
+            let symbol = if removed.has(i) {
+                removed_mark
+            } else if !state.has(i) {
+                empty
+            } else if board.is_poison(i) {
+                poison
+            } else if board.is_forbidden(i) {
+                forbidden
+            } else {
+                block
+            };
+            symbol.to_string()
+        })
+        .collect();
+    // 上のほうの空の層は省く
+    let layer_len = dims[0] * dims.get(1).copied().unwrap_or(1);
+    let occupied = (0..board.tot()).filter(|&i| state.has(i) || removed.has(i)).max();
+    let layers = occupied.map_or(1, |i| i / layer_len + 1);
+    let mut out = grid(&dims[..dims.len().min(2)], dims, &cells[..(layers * layer_len) as usize]);
+    let hidden = board.tot() / layer_len - layers;
+    if hidden > 0 {
+        out.push_str(&format!("（残りの {} 層は空）\n", hidden));
+    }
+    out
+}
+
+fn render_height_map<S: State>(
+    board: &Board,
+    dims: &[u32],
+    state: S,
+    removed: S,
+    charset: Charset,
+) -> Result<String, Error> {
+    let [_, _, poison, _, removed_mark] = charset.symbols();
+    let heights = board.state_to_heights(state)?;
+    let after = board.state_to_heights(state & !removed)?;
+    let base = heights.as_slice().len() as u32;
+    let cells: Vec<String> = (0..base)
+        .map(|b| {
+            let (h, a) = (heights.as_slice()[b as usize], after.as_slice()[b as usize]);
+            let mut text = h.to_string();
+            if a != h {
+                text.push_str(charset.arrow());
+                text.push_str(&a.to_string());
+                text.push_str(removed_mark);
+            }
+            // 柱に残っている毒ブロックがあれば印を付ける
+            if (0..h).any(|z| board.is_poison(b + z * base)) {
+                text.push_str(poison);
+            }
+            text
+        })
+        .collect();
+    // 1 次元の盤面では柱が 1 本だけ
+    let base_dims = if dims.len() > 1 { &dims[..dims.len() - 1] } else { &[1][..] };
+    Ok(grid(&base_dims[..base_dims.len().min(2)], base_dims, &cells))
+}
+
+/// cells を grid_dims（第 0 軸を横、第 1 軸を縦）の格子ごとに区切って並べる。
+/// 格子が複数あれば、それぞれの前に残りの軸の座標を "(x, y, 2)" のような見出しで示す
+fn grid(grid_dims: &[u32], dims: &[u32], cells: &[String]) -> String {
+    let width = cells.iter().map(|c| c.chars().count()).max().unwrap_or(1);
+    let row_len = grid_dims[0] as usize;
+    let grid_len = grid_dims.iter().product::<u32>() as usize;
+    let mut out = String::new();
+    for (g, block) in cells.chunks(grid_len).enumerate() {
+        if cells.len() > grid_len {
+            let mut coord: Vec<String> = ["x", "y"][..grid_dims.len()].iter().map(|a| a.to_string()).collect();
+            coord.extend(Coord::from_index(&dims[grid_dims.len()..], g as u32).0.iter().map(u32::to_string));
+            out.push_str(&format!("({})\n", coord.join(", ")));
+        }
+        for row in block.chunks(row_len) {
+            let row: Vec<String> = row
+                .iter()
+                .map(|c| format!("{}{}", " ".repeat(width - c.chars().count()), c))
+                .collect();
+            out.push_str(row.join(" ").trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// 勝敗の規約。論文によって使われる形式が違うので選べるようにする
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Ruleset {
+    /// 毒ブロック形式（従来の形式）。毒ブロック（既定では原点）は選べず、毒ブロックだけが残った
+    /// 局面で手番のプレイヤーは毒を食べて負ける。毒でない最後のブロックを取った側が勝つ normal play と同じ
+    #[default]
+    Poison,
+    /// 毒ブロックのない normal play。原点も選べ、最後のブロックを取った側が勝つ
+    Normal,
+    /// misère play。原点も選べ、最後のブロックを取った側が負ける。
+    /// 勝敗は Poison と一致するが、Grundy 値や盤面の和での振る舞いは異なる
+    Misere,
+}
+
+impl Ruleset {
+    /// 合法手のない局面で手番のプレイヤーが勝つか（misère では相手が最後のブロックを取っている）
+    pub fn wins_without_moves(self) -> bool {
+        self == Ruleset::Misere
+    }
+}
+
+impl FromStr for Ruleset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Ruleset, Error> {
+        match s {
+            "poison" => Ok(Ruleset::Poison),
+            "normal" => Ok(Ruleset::Normal),
+            "misere" => Ok(Ruleset::Misere),
+            _ => Err(Error::InvalidRules(format!("規約は poison, normal, misere のいずれかです: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for Ruleset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Ruleset::Poison => "poison",
+            Ruleset::Normal => "normal",
+            Ruleset::Misere => "misere",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 毒ブロックを選んだ（取り除くブロック群に毒ブロックが含まれる）ときの扱い
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PoisonMode {
+    /// 毒を食べる手は指せない
+    #[default]
+    Forbidden,
+    /// 毒を食べる手も指せるが、指したプレイヤーがその場で負ける
+    Loses,
+}
+
+impl FromStr for PoisonMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PoisonMode, Error> {
+        match s {
+            "forbid" => Ok(PoisonMode::Forbidden),
+            "lose" => Ok(PoisonMode::Loses),
+            _ => Err(Error::InvalidRules(format!("毒ブロックの扱いは forbid, lose のいずれかです: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for PoisonMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PoisonMode::Forbidden => "forbid",
+            PoisonMode::Loses => "lose",
+        };
+        write!(f, "{}", name)
+    }
+}
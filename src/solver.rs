@@ -0,0 +1,220 @@
+use rayon::prelude::*;
+
+use crate::board::Board;
+use crate::memo::{DenseMemo, Memo};
+use crate::progress::Progress;
+use crate::rank::Ranker;
+use crate::state::State;
+
+/// 探索の並列化のしかた。探索を始めた局面から split_depth 手以内で、ブロックが min_cells 個以上
+/// 残っている局面でだけ合法手を並列に調べ、それより下は一つのスレッドで順に調べる。
+/// 小さな部分木を細かく分けすぎると、タスクの受け渡しの手間や、表に書かれる前の同じ局面を
+/// 複数のスレッドが解く無駄のほうが大きくなる。既定ではどの深さでも並列に調べる
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parallelism {
+    pub split_depth: u32,
+    pub min_cells: u32,
+}
+
+impl Default for Parallelism {
+    fn default() -> Parallelism {
+        Parallelism { split_depth: u32::MAX, min_cells: 0 }
+    }
+}
+
+impl Parallelism {
+    fn splits<S: State>(self, state: S, depth: u32) -> bool {
+        depth < self.split_depth && state.count_ones() >= self.min_cells
+    }
+}
+
+/// 現在の状態 state で、手番のプレイヤーが勝てるかどうかを並列再帰的に判定する関数。
+/// memo は並列安全な表（DashMap や DenseMemo）でメモ化します。
+pub fn win<S: State, M: Memo<S>>(board: &Board, state: S, memo: &M) -> bool {
+    win_with(board, state, memo, Parallelism::default(), None)
+}
+
+/// win と同じだが、parallelism に従って並列化し、progress があれば解いた局面の数と
+/// 並列に分けた深さを記録する
+pub fn win_with<S: State, M: Memo<S>>(
+    board: &Board,
+    state: S,
+    memo: &M,
+    parallelism: Parallelism,
+    progress: Option<&Progress>,
+) -> bool {
+    Search { board, memo, parallelism, progress }.win(state, 0)
+}
+
+/// 探索全体で変わらない引数をまとめたもの
+struct Search<'a, M> {
+    board: &'a Board,
+    memo: &'a M,
+    parallelism: Parallelism,
+    progress: Option<&'a Progress>,
+}
+
+impl<M> Search<'_, M> {
+    /// depth は探索を始めた局面からの手数
+    fn win<S: State>(&self, state: S, depth: u32) -> bool
+    where
+        M: Memo<S>,
+    {
+        let (board, memo) = (self.board, self.memo);
+        if let Some(res) = memo.get(&state) {
+            return res;
+        }
+        let moves = board.legal_moves(state);
+        // 終端状態：合法手がない（毒ブロック形式なら毒ブロックのみが残っている）場合の勝敗は規約と毒の扱いで決まる
+        let winning = if moves.is_empty() {
+            board.wins_without_moves(state)
+        } else if self.parallelism.splits(state, depth) {
+            if let Some(p) = self.progress {
+                p.record_split(depth);
+            }
+            // 合法手について、Rayon の par_iter() を使って並列に再帰的に評価
+            moves.par_iter().any(|&(_, new_state)| !self.win(new_state, depth + 1))
+        } else {
+            moves.iter().any(|&(_, new_state)| !self.win(new_state, depth + 1))
+        };
+        memo.insert(state, winning);
+        if let Some(p) = self.progress {
+            p.record_solved();
+        }
+        winning
+    }
+}
+
+/// 現在の状態から、勝利につながる（必勝となる）手（chosen ブロック）の候補をすべて返す
+pub fn winning_moves<S: State, M: Memo<S>>(board: &Board, state: S, memo: &M) -> Vec<u32> {
+    winning_moves_with(board, state, memo, Parallelism::default(), None)
+}
+
+/// winning_moves と同じだが、指した後の局面を win_with で parallelism に従って解き、progress に記録する
+pub fn winning_moves_with<S: State, M: Memo<S>>(
+    board: &Board,
+    state: S,
+    memo: &M,
+    parallelism: Parallelism,
+    progress: Option<&Progress>,
+) -> Vec<u32> {
+    board
+        .legal_moves(state)
+        .into_iter()
+        .filter_map(|(mv, new_state)| {
+            if !win_with(board, new_state, memo, parallelism, progress) { Some(mv) } else { None }
+        })
+        .collect()
+}
+
+/// エンジンの指し手を返す。必勝手があれば最初の必勝手を、なければ取り除くブロックが
+/// 最も少ない手（負けをできるだけ先に延ばす手）を返す。合法手がなければ None。
+/// 局面は parallelism に従って並列に解く
+pub fn engine_move<S: State, M: Memo<S>>(board: &Board, state: S, memo: &M, parallelism: Parallelism) -> Option<u32> {
+    winning_moves_with(board, state, memo, parallelism, None).first().copied().or_else(|| {
+        board
+            .legal_moves(state)
+            .into_iter()
+            .max_by_key(|&(mv, new_state)| (new_state.count_ones(), std::cmp::Reverse(mv)))
+            .map(|(mv, _)| mv)
+    })
+}
+
+/// 後退解析で一度に並べ替える局面の数
+const RETROGRADE_CHUNK: u64 = 1 << 16;
+
+/// 後退解析（retrograde analysis）で盤面のすべての局面に勝敗を付ける。
+///
+/// 手を指すと最後の軸方向の各層が小さくなるので、局面の順位は真に小さくなる。そこで局面を
+/// 順位の順に一定数ずつ区切って処理し、区切りの中ではブロック数の少ない順に並べ替える。
+/// 各局面は手を指した後の（すでに勝敗の付いた）局面だけから P/N を決め、同じ区切りの同じ
+/// ブロック数の局面どうしは互いに行き来しないので並列に処理できる。再帰しないので深さの制限がなく、
+/// 表のほかには区切り 1 つ分の局面しか持たないので、使うメモリは局面数で決まる。
+/// 毒ブロックを含まない order ideal（空の盤面など）は局面ではなく、合法手で移ることもないので表に入れない。
+/// 戻り値の表にはすべての局面の勝敗が入っている。
+pub fn retrograde<S: State>(board: &Board, ranker: Ranker) -> DenseMemo {
+    let memo = DenseMemo::new(ranker);
+    retrograde_into::<S>(board, &memo, None);
+    memo
+}
+
+/// retrograde と同じだが、表 memo に勝敗がすでにある局面（前の盤面から引き継いだ局面など）は解き直さない。
+/// progress を渡すと、順位の区切りを 1 つ解くごとにその区切りで解いた局面数を記録する
+pub fn retrograde_into<S: State>(board: &Board, memo: &DenseMemo, progress: Option<&Progress>) {
+    let ranker = memo.ranker();
+    let mut start = 0;
+    while start < ranker.count() {
+        let end = (start + RETROGRADE_CHUNK).min(ranker.count());
+        let mut states: Vec<S> = (start..end)
+            .into_par_iter()
+            .filter(|&r| !memo.known(r))
+            .map(|r| ranker.unrank(r))
+            .filter(|&state| board.contains_poison(state))
+            .collect();
+        states.sort_by_key(|state| state.count_ones());
+        for group in states.chunk_by(|a, b| a.count_ones() == b.count_ones()) {
+            group.par_iter().for_each(|&state| {
+                // 合法手のない局面（毒ブロックのみや、毒のない規約での空の盤面）の勝敗は規約と毒の扱いで決まる
+                let moves = board.legal_moves(state);
+                let winning = if moves.is_empty() {
+                    board.wins_without_moves(state)
+                } else {
+                    moves.iter().any(|(_, new_state)| !memo.get(new_state).unwrap())
+                };
+                memo.insert(state, winning);
+            });
+        }
+        if let Some(progress) = progress {
+            progress.record_solved_batch(states.len() as u64);
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{PoisonMode, Ruleset};
+    use dashmap::DashMap;
+
+    #[test]
+    fn retrograde_agrees_with_search() {
+        for dims in [&[2, 2, 3][..], &[3, 4], &[2, 3, 4], &[2, 2, 2, 2]] {
+            let board = Board::new(dims.to_vec()).unwrap();
+            let table = retrograde::<u128>(&board, Ranker::new(&board).unwrap());
+            let memo = DashMap::new();
+            let mut checked = 0;
+            table.for_each_entry(&mut |state: u128, winning| {
+                assert_eq!(winning, win(&board, state, &memo), "{:?}", dims);
+                checked += 1;
+            });
+            // 空の盤面以外のすべての order ideal
+            assert_eq!(checked, table.ranker().count() - 1);
+        }
+    }
+
+    #[test]
+    fn retrograde_skips_non_positions() {
+        // 2x2x3 の order ideal 50 個のうち、空の盤面は毒ブロックがないので局面ではない
+        let board = Board::new(vec![2, 2, 3]).unwrap();
+        let table = retrograde::<u128>(&board, Ranker::new(&board).unwrap());
+        assert_eq!(table.ranker().count(), 50);
+        assert_eq!(Memo::<u128>::entries(&table), 49);
+        assert_eq!(table.get(&0u128), None);
+        assert_eq!(table.losing_count(), 9);
+    }
+
+    #[test]
+    fn only_poisoned_moves_lose_in_lose_mode() {
+        // 長さ 2 の盤面で奥のブロックが毒なら、どの手も毒を食べる
+        let board = Board::new(vec![2]).unwrap().with_ruleset(Ruleset::Misere).with_poison(vec![1]);
+        let state: u128 = board.full_state();
+        // forbid では合法手のない局面なので misère の規約どおり手番の勝ち、lose では毒を食べて負け
+        assert!(win(&board, state, &DashMap::new()));
+        let board = board.with_poison_mode(PoisonMode::Loses);
+        assert!(!win(&board, state, &DashMap::new()));
+        let ranker = Ranker::new(&board).unwrap();
+        assert_eq!(retrograde::<u128>(&board, ranker).get(&state), Some(false));
+        assert_eq!(crate::grundy::misere_grundy(&board, state, &DashMap::new()), 0);
+    }
+}
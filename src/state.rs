@@ -0,0 +1,141 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{BitAnd, BitOr, Not};
+
+/// 各ブロックの存在をビットで表す状態の型。
+/// ビット i がインデックス i のブロックに対応する。
+pub trait State:
+    Copy + Eq + Hash + Debug + Send + Sync
+    + BitAnd<Output = Self> + BitOr<Output = Self> + Not<Output = Self>
+{
+    /// 表現できるブロック数の上限
+    const CAPACITY: u32;
+
+    /// どのブロックも存在しない状態
+    fn zero() -> Self;
+
+    /// ブロック i だけが存在する状態（i < CAPACITY）
+    fn bit(i: u32) -> Self;
+
+    /// 存在するブロック数
+    fn count_ones(self) -> u32;
+
+    fn is_zero(self) -> bool {
+        self == Self::zero()
+    }
+
+    /// ブロック i が存在するか
+    fn has(self, i: u32) -> bool {
+        !(self & Self::bit(i)).is_zero()
+    }
+
+    /// 下位 n ビットがすべて 1 の状態
+    fn low_bits(n: u32) -> Self {
+        (0..n).fold(Self::zero(), |s, i| s | Self::bit(i))
+    }
+
+    /// 64 ブロックずつ下位から並べた語の並びから状態を作る。CAPACITY を超える分は捨てる
+    fn from_words(words: &[u64]) -> Self;
+}
+
+/// ブロックの集合を、64 ブロックずつ下位から並べた語の並び（State::from_words で状態にできる）にする
+pub fn cell_words(tot: u32, cells: impl IntoIterator<Item = u32>) -> Vec<u64> {
+    let mut words = vec![0; tot.div_ceil(64) as usize];
+    for i in cells {
+        words[(i / 64) as usize] |= 1 << (i % 64);
+    }
+    words
+}
+
+impl State for u128 {
+    const CAPACITY: u32 = 128;
+
+    fn zero() -> Self {
+        0
+    }
+
+    fn bit(i: u32) -> Self {
+        1 << i
+    }
+
+    fn count_ones(self) -> u32 {
+        u128::count_ones(self)
+    }
+
+    fn low_bits(n: u32) -> Self {
+        if n == 0 { 0 } else { u128::MAX >> (128 - n) }
+    }
+
+    fn from_words(words: &[u64]) -> Self {
+        words.iter().take(2).enumerate().fold(0, |s, (k, &w)| s | (w as u128) << (64 * k))
+    }
+}
+
+/// u64 を W 語並べた固定長ビット集合。128 ブロックを超える盤面用
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Bits<const W: usize>(pub [u64; W]);
+
+impl<const W: usize> State for Bits<W> {
+    const CAPACITY: u32 = 64 * W as u32;
+
+    fn zero() -> Self {
+        Bits([0; W])
+    }
+
+    fn bit(i: u32) -> Self {
+        let mut words = [0; W];
+        words[(i / 64) as usize] = 1 << (i % 64);
+        Bits(words)
+    }
+
+    fn count_ones(self) -> u32 {
+        self.0.iter().map(|w| w.count_ones()).sum()
+    }
+
+    fn has(self, i: u32) -> bool {
+        self.0[(i / 64) as usize] & (1 << (i % 64)) != 0
+    }
+
+    fn from_words(words: &[u64]) -> Self {
+        let mut bits = [0; W];
+        let len = words.len().min(W);
+        bits[..len].copy_from_slice(&words[..len]);
+        Bits(bits)
+    }
+}
+
+impl<const W: usize> BitAnd for Bits<W> {
+    type Output = Self;
+
+    fn bitand(mut self, rhs: Self) -> Self {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a &= b;
+        }
+        self
+    }
+}
+
+impl<const W: usize> BitOr for Bits<W> {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a |= b;
+        }
+        self
+    }
+}
+
+impl<const W: usize> Not for Bits<W> {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        for a in self.0.iter_mut() {
+            *a = !*a;
+        }
+        self
+    }
+}
+
+/// 用意している状態型のうち最大のもの（Bits<16>）で扱えるブロック数
+pub const MAX_CELLS: u32 = Bits::<16>::CAPACITY;
@@ -0,0 +1,207 @@
+use dashmap::DashMap;
+use rayon::prelude::*;
+
+use crate::board::Board;
+use crate::grundy::grundy;
+use crate::rules::Ruleset;
+use crate::state::State;
+
+/// 和の中での一手：どの盤面（component）のどのブロック（cell、盤面でのインデックス）を選ぶか
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SumMove {
+    pub component: usize,
+    pub cell: u32,
+}
+
+/// 独立な複数の Chomp 盤面の直和（disjunctive sum）。
+/// 手番のプレイヤーはちょうど一つの盤面を選んで、その盤面で一手指す。
+/// どの盤面にも合法手がなくなったとき、手番のプレイヤーの負け（misère の盤面を含むなら勝ち。
+/// ただし PoisonMode::Loses の盤面に毒を食べる手が残っていれば、それを選ぶしかないので負け）。
+/// Grundy 値による解法は normal play（毒ブロック形式を含む）の和でのみ正しいので、
+/// misère の盤面を含む和の勝敗と必勝手は直接探索で求める。
+pub struct GameSum<S: State> {
+    components: Vec<(Board, S)>,
+    /// 盤面ごとの Grundy 値の表。盤面の形が違うと同じビット列でも別の局面なので分けて持つ
+    grundy_memos: Vec<DashMap<S, u32>>,
+    /// 直接探索の表。各盤面の状態を並べたものをキーにする
+    search_memo: DashMap<Vec<S>, bool>,
+}
+
+impl<S: State> GameSum<S> {
+    /// (盤面, 状態) の組の並びから和を作る
+    pub fn new(components: Vec<(Board, S)>) -> GameSum<S> {
+        let grundy_memos = components.iter().map(|_| DashMap::new()).collect();
+        GameSum { components, grundy_memos, search_memo: DashMap::new() }
+    }
+
+    /// 各盤面の状態
+    pub fn states(&self) -> Vec<S> {
+        self.components.iter().map(|&(_, s)| s).collect()
+    }
+
+    /// 盤面 i の Grundy 値
+    pub fn component_grundy(&self, i: usize) -> u32 {
+        let (board, state) = &self.components[i];
+        grundy(board, *state, &self.grundy_memos[i])
+    }
+
+    /// 和の Grundy 値。Sprague–Grundy の定理により各盤面の Grundy 値の排他的論理和
+    pub fn grundy(&self) -> u32 {
+        (0..self.components.len()).fold(0, |acc, i| acc ^ self.component_grundy(i))
+    }
+
+    /// misère の盤面を含むか
+    fn misere(&self) -> bool {
+        self.components.iter().any(|(b, _)| b.ruleset() == Ruleset::Misere)
+    }
+
+    /// 手番のプレイヤーが勝てるか（Grundy 値が 0 でないか）
+    pub fn win(&self) -> bool {
+        if self.misere() {
+            return self.win_by_search();
+        }
+        self.grundy() != 0
+    }
+
+    /// 勝利につながる手をすべて返す。和の Grundy 値を g、盤面 i の Grundy 値を g_i とすると、
+    /// 盤面 i で Grundy 値 g ^ g_i の局面に移る手がちょうど必勝手になる
+    pub fn winning_moves(&self) -> Vec<SumMove> {
+        if self.misere() {
+            return self.winning_moves_by_search();
+        }
+        let total = self.grundy();
+        let mut moves = Vec::new();
+        if total == 0 {
+            return moves;
+        }
+        for (i, (board, state)) in self.components.iter().enumerate() {
+            let target = total ^ self.component_grundy(i);
+            for (cell, new_state) in board.legal_moves(*state) {
+                if grundy(board, new_state, &self.grundy_memos[i]) == target {
+                    moves.push(SumMove { component: i, cell });
+                }
+            }
+        }
+        moves
+    }
+
+    /// Grundy 値を使わず、和の局面全体を直接探索して手番のプレイヤーが勝てるかを判定する。
+    /// 状態空間は各盤面の局面数の積になるので、Grundy 値による結果の確認用
+    pub fn win_by_search(&self) -> bool {
+        self.search(self.states())
+    }
+
+    /// 直接探索で勝利につながる手をすべて返す
+    pub fn winning_moves_by_search(&self) -> Vec<SumMove> {
+        let states = self.states();
+        self.moves(&states)
+            .into_iter()
+            .filter_map(|(mv, next)| if !self.search(next) { Some(mv) } else { None })
+            .collect()
+    }
+
+    /// 和の局面 states から指せる手と、指した後の局面
+    fn moves(&self, states: &[S]) -> Vec<(SumMove, Vec<S>)> {
+        let mut moves = Vec::new();
+        for (i, (board, _)) in self.components.iter().enumerate() {
+            for (cell, new_state) in board.legal_moves(states[i]) {
+                let mut next = states.to_vec();
+                next[i] = new_state;
+                moves.push((SumMove { component: i, cell }, next));
+            }
+        }
+        moves
+    }
+
+    fn search(&self, states: Vec<S>) -> bool {
+        if let Some(res) = self.search_memo.get(&states) {
+            return *res;
+        }
+        // win と同じく Rayon で並列に評価する
+        let moves = self.moves(&states);
+        let winning = if moves.is_empty() {
+            let must_eat_poison = self.components.iter().zip(&states).any(|((b, _), &s)| !b.poisoned_moves(s).is_empty());
+            self.misere() && !must_eat_poison
+        } else {
+            moves.into_par_iter().any(|(_, next)| !self.search(next))
+        };
+        self.search_memo.insert(states, winning);
+        winning
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::PoisonMode;
+    use crate::solver::win;
+
+    /// 各盤面の全ブロックの局面の和
+    fn sum(boards: Vec<Board>) -> GameSum<u128> {
+        GameSum::new(boards.into_iter().map(|b| (b.clone(), b.full_state())).collect())
+    }
+
+    fn boxes(dims: &[&[u32]]) -> Vec<Board> {
+        dims.iter().map(|d| Board::new(d.to_vec()).unwrap()).collect()
+    }
+
+    /// Grundy 値による勝敗と必勝手が直接探索と一致することを確かめ、勝敗を返す
+    fn check(game: &GameSum<u128>) -> bool {
+        let key = |mv: &SumMove| (mv.component, mv.cell);
+        let mut moves = game.winning_moves();
+        let mut by_search = game.winning_moves_by_search();
+        moves.sort_by_key(key);
+        by_search.sort_by_key(key);
+        assert_eq!(moves, by_search);
+        assert_eq!(game.win(), game.win_by_search());
+        assert_eq!(game.win(), !moves.is_empty());
+        game.win()
+    }
+
+    #[test]
+    fn grundy_agrees_with_search() {
+        let sums: [&[&[u32]]; 5] = [
+            &[&[2, 3], &[3]],
+            &[&[2, 2], &[2, 2]],
+            &[&[3, 3], &[2], &[4]],
+            &[&[2, 2, 2], &[1, 3]],
+            &[&[2, 3], &[2, 3], &[1, 2]],
+        ];
+        let results: Vec<bool> = sums.iter().map(|dims| check(&sum(boxes(dims)))).collect();
+        // 同じ盤面 2 つの和は後手必勝
+        assert!(!results[1]);
+        assert!(results.iter().any(|&w| w));
+    }
+
+    #[test]
+    fn grundy_agrees_with_search_with_poison_lose_mode() {
+        // 1 列 3 ブロックで真ん中が毒の盤面。奥のブロックを取ると毒を食べる手しか残らない
+        let line = Board::new(vec![3]).unwrap().with_poison(vec![1]).with_poison_mode(PoisonMode::Loses);
+        let game = sum(vec![line.clone()]);
+        assert_eq!(game.grundy(), 1);
+        assert!(check(&game));
+        for other in boxes(&[&[2], &[3], &[2, 2], &[2, 3]]) {
+            check(&sum(vec![line.clone(), other]));
+        }
+        check(&sum(vec![line.clone(), line]));
+    }
+
+    #[test]
+    fn misere_sums_are_searched() {
+        let misere = |dims: &[u32]| Board::new(dims.to_vec()).unwrap().with_ruleset(Ruleset::Misere);
+        // 盤面 1 つなら盤面単体の勝敗と同じ
+        for dims in [&[2, 3][..], &[3, 3], &[1], &[2, 2, 2]] {
+            let board = misere(dims);
+            let game = sum(vec![board.clone()]);
+            assert_eq!(game.win(), win(&board, board.full_state::<u128>(), &DashMap::new()), "{:?}", dims);
+            check(&game);
+        }
+        // 1 ブロックの misère の盤面 2 つ: 手番は 1 つ取り、相手が最後のブロックを取って負ける
+        assert!(check(&sum(vec![misere(&[1]), misere(&[1])])));
+        check(&sum(vec![misere(&[2, 3]), Board::new(vec![3]).unwrap()]));
+        // misère でも、毒を食べる手しか残っていなければ手番の負け
+        let forced = misere(&[2]).with_poison(vec![1]).with_poison_mode(PoisonMode::Loses);
+        assert!(!check(&sum(vec![forced.clone()])));
+        check(&sum(vec![forced, misere(&[2])]));
+    }
+}
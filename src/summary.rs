@@ -0,0 +1,66 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::memo::Memo;
+use crate::progress::format_bytes;
+use crate::state::State;
+
+/// 解き終えた表の集計: P 局面と N 局面の数、表の大きさと計算時間
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Summary {
+    /// 表に負け（手番の側が負ける）として記録された局面の数
+    pub p: u64,
+    /// 表に勝ちとして記録された局面の数
+    pub n: u64,
+    /// 表のおおよそのバイト数
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Summary {
+    /// memo の局面を勝敗ごとに数える。elapsed は表を作るのにかかった時間
+    pub fn new<S: State, M: Memo<S>>(memo: &M, elapsed: Duration) -> Summary {
+        let (mut p, mut n) = (0u64, 0u64);
+        memo.for_each_entry(&mut |_, win| if win { n += 1 } else { p += 1 });
+        Summary { p, n, bytes: memo.approx_bytes(), elapsed }
+    }
+
+    /// 表の局面の数
+    pub fn positions(&self) -> u64 {
+        self.p + self.n
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "集計: 表の局面 {}（P 局面 {}、N 局面 {}）、表の大きさ 約 {}、計算時間 {:.2} 秒",
+            self.positions(),
+            self.p,
+            self.n,
+            format_bytes(self.bytes),
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::rank::Ranker;
+    use crate::solver::retrograde;
+
+    #[test]
+    fn counts_p_and_n_positions() {
+        // 2x2x3 の局面 49 個のうち P 局面は 9 個
+        let board = Board::new(vec![2, 2, 3]).unwrap();
+        let table = retrograde::<u128>(&board, Ranker::new(&board).unwrap());
+        let summary = Summary::new::<u128, _>(&table, Duration::from_millis(1500));
+        assert_eq!((summary.p, summary.n, summary.positions()), (9, 40, 49));
+        assert_eq!(summary.bytes, Memo::<u128>::approx_bytes(&table));
+        assert!(summary.to_string().contains("P 局面 9、N 局面 40"));
+        assert!(summary.to_string().ends_with("計算時間 1.50 秒"));
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::error::Error;
+use crate::state::State;
+
+/// 立方体 1 個の辺の長さ（SVG の座標単位）
+const SCALE: f64 = 24.0;
+/// 余白
+const MARGIN: f64 = 12.0;
+/// 凡例 1 行の高さ
+const LINE_HEIGHT: f64 = 18.0;
+
+/// 面の色（上面, 右の側面, 左の側面）
+const BLOCK_COLORS: [&str; 3] = ["#e8e8e8", "#c4c4c4", "#a0a0a0"];
+const POISON_COLORS: [&str; 3] = ["#f06060", "#c83838", "#a02020"];
+const FORBIDDEN_COLORS: [&str; 3] = ["#a8c0e0", "#809cc0", "#6078a0"];
+const MARKED_COLORS: [&str; 3] = ["#ffe060", "#e0b830", "#c09010"];
+
+/// 盤面が SVG に描ける（3 次元以下の箱である）か確かめ、各辺の長さを返す。
+/// 解く前に確かめられるよう isometric_svg とは別に公開する
+pub fn drawable_dims(board: &Board) -> Result<&[u32], Error> {
+    match board.dims() {
+        Some(dims) if dims.len() <= 3 => Ok(dims),
+        _ => Err(Error::InvalidShape(format!(
+            "盤面 {} は 3 次元以下の箱ではないので SVG に描けません",
+            board
+        ))),
+    }
+}
+
+/// 3 次元以下の箱の盤面の局面 state を等角投影で描いた SVG を返す。
+/// 原点（既定の毒ブロック）が手前の下に来る向きで描き、毒ブロックは赤、選べないブロックは青、
+/// marked のブロック（必勝手など）は黄色で塗り、marked は図の下に凡例として名前も並べる。
+/// 箱でない盤面や 4 次元以上の盤面はエラー
+pub fn isometric_svg<S: State>(board: &Board, state: S, marked: &[u32]) -> Result<String, Error> {
+    let dims = drawable_dims(board)?;
+    // 足りない軸は長さ 1 として 3 次元の箱にそろえる
+    let [x_dim, y_dim, z_dim] = [0, 1, 2].map(|k| dims.get(k).copied().unwrap_or(1));
+
+    // 原点が手前に来るよう x, y を反転した座標 (x', y', z) を、x' - y' を横、x' + y' と z を縦に投影する
+    let project = |x: f64, y: f64, z: f64| -> (f64, f64) {
+        let (x, y) = (x_dim as f64 - x, y_dim as f64 - y);
+        ((x - y) * SCALE * 0.866, (x + y) * SCALE * 0.5 - z * SCALE)
+    };
+    let min_u = project(x_dim as f64, 0.0, 0.0).0;
+    let max_u = project(0.0, y_dim as f64, 0.0).0;
+    let min_v = project(x_dim as f64, y_dim as f64, z_dim as f64).1;
+    let max_v = project(0.0, 0.0, 0.0).1;
+    let width = max_u - min_u + 2.0 * MARGIN;
+    let picture_height = max_v - min_v + 2.0 * MARGIN;
+    let height = picture_height + LINE_HEIGHT * (marked.len() as f64 + 1.0);
+    let (du, dv) = (MARGIN - min_u, MARGIN - min_v);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" viewBox="0 0 {:.1} {:.1}">"#,
+        width, height, width, height
+    );
+    let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#ffffff"/>"##);
+
+    // 画家のアルゴリズム: 奥（x' + y' + z が小さい）の立方体から順に描く
+    let mut cells: Vec<(u32, [u32; 3])> = (0..board.tot())
+        .filter(|&i| state.has(i))
+        .map(|i| {
+            let coord = board.index_to_coord(i).unwrap();
+            (i, [0, 1, 2].map(|k| coord.0.get(k).copied().unwrap_or(0)))
+        })
+        .collect();
+    cells.sort_by_key(|&(_, [x, y, z])| ((x_dim - x) + (y_dim - y) + z, z));
+    for (i, [x, y, z]) in cells {
+        let colors = if marked.contains(&i) {
+            MARKED_COLORS
+        } else if board.is_poison(i) {
+            POISON_COLORS
+        } else if board.is_forbidden(i) {
+            FORBIDDEN_COLORS
+        } else {
+            BLOCK_COLORS
+        };
+        let (x, y, z) = (x as f64, y as f64, z as f64);
+        // 見える面は上面と、原点側を向いた 2 つの側面
+        let faces = [
+            [(x, y, z + 1.0), (x + 1.0, y, z + 1.0), (x + 1.0, y + 1.0, z + 1.0), (x, y + 1.0, z + 1.0)],
+            [(x, y, z), (x, y + 1.0, z), (x, y + 1.0, z + 1.0), (x, y, z + 1.0)],
+            [(x, y, z), (x + 1.0, y, z), (x + 1.0, y, z + 1.0), (x, y, z + 1.0)],
+        ];
+        let _ = writeln!(svg, r#"<g><title>{}</title>"#, escape(board.cell_name(i)));
+        for (face, color) in faces.iter().zip(colors) {
+            let points: Vec<String> = face
+                .iter()
+                .map(|&(x, y, z)| {
+                    let (u, v) = project(x, y, z);
+                    format!("{:.1},{:.1}", u + du, v + dv)
+                })
+                .collect();
+            let _ = writeln!(
+                svg,
+                r##"<polygon points="{}" fill="{}" stroke="#404040" stroke-width="0.8"/>"##,
+                points.join(" "),
+                color
+            );
+        }
+        let _ = writeln!(svg, "</g>");
+    }
+
+    // 凡例
+    let mut y = picture_height + LINE_HEIGHT * 0.5;
+    let _ = writeln!(
+        svg,
+        r#"<text x="{:.1}" y="{:.1}" font-family="sans-serif" font-size="12">{}</text>"#,
+        MARGIN,
+        y,
+        escape(&format!("盤面 {}（規約 {}）", board, board.ruleset()))
+    );
+    for &i in marked {
+        y += LINE_HEIGHT;
+        let _ = writeln!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" font-family="sans-serif" font-size="12" fill="#a07000">{}</text>"##,
+            MARGIN,
+            y,
+            escape(&format!("必勝手 {}", board.cell_name(i)))
+        );
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// XML の特殊文字をエスケープする
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
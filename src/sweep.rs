@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::board::Board;
+use crate::memo::{DenseMemo, Memo};
+use crate::rank::Ranker;
+use crate::solver::{retrograde_into, win_with, winning_moves_with, Parallelism};
+use crate::state::State;
+
+/// 大きさを変えながら解いた盤面 1 つの結果
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SweepResult {
+    /// 全ブロックの局面が先手必勝か
+    pub first_win: bool,
+    /// 全ブロックの局面の必勝手
+    pub moves: Vec<u32>,
+    /// P 局面の数。後退解析で全局面を解いた盤面だけ分かる
+    pub losing: Option<u64>,
+    /// 前の盤面から引き継いだ局面の数
+    pub reused: u64,
+    pub elapsed: Duration,
+}
+
+/// 大きさの違う盤面を順に解く。
+/// 順位付けできる盤面は後退解析ですべての局面を 1 局面 2 ビットの表に解いて P 局面を数え、
+/// それ以外の盤面は初期局面から再帰的に探索する。
+/// 前の盤面が部分箱なら（2x3x(n-1) と 2x3xn など）その表を引き継ぎ、解いた局面を解き直さない
+pub struct Sweep<'a, S> {
+    parallelism: Parallelism,
+    /// 前に解いた盤面とその表
+    prev: Option<(&'a Board, Box<dyn Memo<S> + 'a>)>,
+}
+
+impl<'a, S: State + 'a> Sweep<'a, S> {
+    pub fn new(parallelism: Parallelism) -> Sweep<'a, S> {
+        Sweep { parallelism, prev: None }
+    }
+
+    /// 盤面 board の全ブロックの局面を解き、その表を次の盤面のために覚えておく
+    pub fn solve(&mut self, board: &'a Board) -> SweepResult {
+        let start = Instant::now();
+        let state: S = board.full_state();
+        let prev = &self.prev;
+        let carry = |into: &dyn Memo<S>| prev.as_ref().map_or(0, |(sub, memo)| board.carry_over(sub, &**memo, into));
+        let (first_win, moves, losing, reused, memo): (_, _, _, _, Box<dyn Memo<S> + 'a>) = match Ranker::new(board) {
+            Ok(ranker) => {
+                let memo = DenseMemo::new(ranker);
+                let reused = carry(&memo);
+                retrograde_into::<S>(board, &memo, None);
+                let first_win = memo.get(&state).unwrap();
+                let moves = winning_moves_with(board, state, &memo, self.parallelism, None);
+                (first_win, moves, Some(memo.losing_count()), reused, Box::new(memo))
+            }
+            Err(_) => {
+                let memo = DashMap::new();
+                let reused = carry(&memo);
+                let first_win = win_with(board, state, &memo, self.parallelism, None);
+                let moves = winning_moves_with(board, state, &memo, self.parallelism, None);
+                (first_win, moves, None, reused, Box::new(memo))
+            }
+        };
+        let elapsed = start.elapsed();
+        self.prev = Some((board, memo));
+        SweepResult { first_win, moves, losing, reused, elapsed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{win, winning_moves};
+
+    #[test]
+    fn sweeps_reuse_the_previous_table() {
+        let boards: Vec<Board> = (1..=4).map(|n| Board::new(vec![2, 2, n]).unwrap()).collect();
+        let mut sweep = Sweep::<u128>::new(Parallelism::default());
+        let results: Vec<SweepResult> = boards.iter().map(|board| sweep.solve(board)).collect();
+        // 2x2x1, 2x2x2, 2x2x3 の P 局面は 2, 5, 9 個
+        assert_eq!(results[..3].iter().map(|r| r.losing).collect::<Vec<_>>(), [Some(2), Some(5), Some(9)]);
+        assert_eq!(results[0].reused, 0);
+        assert!(results[1..].iter().all(|r| r.reused > 0));
+        for (board, result) in boards.iter().zip(&results) {
+            let memo = DashMap::new();
+            assert_eq!(result.first_win, win(board, board.full_state::<u128>(), &memo));
+            assert_eq!(result.moves, winning_moves(board, board.full_state::<u128>(), &memo));
+        }
+    }
+}
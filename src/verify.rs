@@ -0,0 +1,132 @@
+use dashmap::DashMap;
+
+use crate::board::Board;
+use crate::db::load;
+use crate::error::Error;
+use crate::memo::{DenseMemo, Memo};
+use crate::rank::Ranker;
+use crate::solver::{retrograde, win_with, winning_moves_with, Parallelism};
+use crate::state::State;
+
+/// 照合 1 つの結果
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    /// 何と何を照らし合わせたか
+    pub name: String,
+    /// 結果が食い違った局面の数
+    pub mismatched: u64,
+    /// 照らし合わせた局面の数
+    pub total: u64,
+}
+
+/// verify の結果
+#[derive(Clone, Debug)]
+pub struct Verification {
+    /// 再帰的な探索（DashMap）で求めた、局面が先手必勝か
+    pub first_win: bool,
+    /// 再帰的な探索（DashMap）で求めた必勝手
+    pub moves: Vec<u32>,
+    pub checks: Vec<Check>,
+    /// 順位付けできず後退解析との照合を省いたなら、その理由
+    pub skipped: Option<Error>,
+}
+
+impl Verification {
+    /// 食い違いのあった照合の名前
+    pub fn failures(&self) -> Vec<&str> {
+        self.checks.iter().filter(|c| c.mismatched > 0).map(|c| c.name.as_str()).collect()
+    }
+}
+
+/// 再帰的な探索（DashMap と、順位付けできれば順位の表）と後退解析、db を指定していればそのデータベースで
+/// 局面 state の勝敗と必勝手、解いた局面の勝敗が一致するか確かめる。
+/// 食い違いは結果の checks に記録し、エラーにするのはデータベースを読めないときだけ
+pub fn verify<S: State>(
+    board: &Board,
+    state: S,
+    parallelism: Parallelism,
+    db: Option<&str>,
+) -> Result<Verification, Error> {
+    let hash = DashMap::new();
+    let expected = (
+        win_with(board, state, &hash, parallelism, None),
+        winning_moves_with(board, state, &hash, parallelism, None),
+    );
+    let mut checks = Vec::new();
+    let mut check = |name: &str, mismatched: u64, total: u64| {
+        checks.push(Check { name: name.to_string(), mismatched, total });
+    };
+    let same = |memo: &dyn Fn(S) -> bool| {
+        let moves: Vec<u32> = board.legal_moves(state).into_iter().filter(|&(_, s)| !memo(s)).map(|(mv, _)| mv).collect();
+        (memo(state), moves) == expected
+    };
+
+    let skipped = match Ranker::new(board) {
+        Ok(ranker) => {
+            let dense = DenseMemo::new(ranker.clone());
+            let ok = same(&|s| win_with(board, s, &dense, parallelism, None));
+            check("再帰的な探索（dense）の初期局面と必勝手", !ok as u64, 1);
+            let table = retrograde::<S>(board, ranker);
+            let ok = same(&|s| table.get(&s).unwrap());
+            check("後退解析の初期局面と必勝手", !ok as u64, 1);
+            // 再帰的な探索で解いたすべての局面を後退解析の表と照らし合わせる
+            let (mut mismatched, mut total) = (0, 0);
+            hash.for_each_entry(&mut |state, win| {
+                total += 1;
+                mismatched += (table.get(&state) != Some(win)) as u64;
+            });
+            check("再帰的な探索と後退解析の表", mismatched, total);
+            None
+        }
+        Err(e) => Some(e),
+    };
+
+    if let Some(path) = db {
+        let loaded = DashMap::new();
+        load(path, board, &loaded)?;
+        let (mut mismatched, mut total) = (0, 0);
+        loaded.for_each_entry(&mut |state, win| {
+            total += 1;
+            mismatched += (win_with(board, state, &hash, parallelism, None) != win) as u64;
+        });
+        check(&format!("データベース {}", path), mismatched, total);
+    }
+
+    let (first_win, moves) = expected;
+    Ok(Verification { first_win, moves, checks, skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::save;
+    use crate::solver::win;
+
+    #[test]
+    fn solvers_agree() {
+        let board = Board::new(vec![2, 3, 4]).unwrap();
+        let result = verify::<u128>(&board, board.full_state(), Parallelism::default(), None).unwrap();
+        assert!(result.first_win);
+        assert_eq!(result.checks.len(), 3);
+        assert!(result.failures().is_empty());
+        assert!(result.skipped.is_none());
+    }
+
+    #[test]
+    fn wrong_databases_are_reported() {
+        let board = Board::new(vec![2, 2, 3]).unwrap();
+        let memo: DashMap<u128, bool> = DashMap::new();
+        win(&board, board.full_state(), &memo);
+        // 1 局面の勝敗を反転させたデータベース
+        let flipped = *memo.iter().next().unwrap().key();
+        memo.alter(&flipped, |_, win| !win);
+        let path = std::env::temp_dir().join(format!("chomp-verify-{}.db", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        save(&path, &board, &memo).unwrap();
+        let result = verify::<u128>(&board, board.full_state(), Parallelism::default(), Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let db = result.checks.last().unwrap();
+        assert_eq!((db.mismatched, db.total), (1, memo.len() as u64));
+        assert_eq!(result.failures(), [db.name.as_str()]);
+    }
+}